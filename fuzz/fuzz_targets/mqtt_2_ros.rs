@@ -0,0 +1,55 @@
+#![no_main]
+
+//! feeds arbitrary `/get` payloads through the decoding path of every module,
+//! run with `cargo +nightly fuzz run mqtt_2_ros` from the repository root
+
+use coffee_maker_driver::module_layout::ModuleLayouts;
+use coffee_maker_driver::module_msg_converter::{ModuleMsgConverter, ModuleOutputMsg};
+use libfuzzer_sys::fuzz_target;
+use obd_coffee_maker_interface::msg::{
+    CapsuleFeederOutput, CoffeeFeederOutput, CupHolderOutput, LightOutput, PDUOutput, TankOutput,
+};
+use std::sync::OnceLock;
+
+const MODULE_LAYOUTS: &str = include_str!("../../config/modules.toml");
+
+type DecodeFn = fn(&ModuleMsgConverter, &str);
+
+fn decode<T: ModuleOutputMsg>(converter: &ModuleMsgConverter, frame: &str) {
+    let _ = converter.decode_get_str::<T>(frame);
+    // also reach the field decoding of frames that would fail the checksum
+    let _ = converter.decode_output::<T>(frame);
+    let _ = converter.payload_from_full_output_format_string(frame);
+}
+
+fn converters() -> &'static Vec<(ModuleMsgConverter, DecodeFn)> {
+    static CONVERTERS: OnceLock<Vec<(ModuleMsgConverter, DecodeFn)>> = OnceLock::new();
+    CONVERTERS.get_or_init(|| {
+        let layouts = ModuleLayouts::from_toml(MODULE_LAYOUTS).expect("module layouts are valid");
+        let modules: [(&str, DecodeFn); 6] = [
+            ("coffee_feeder", decode::<CoffeeFeederOutput>),
+            ("capsule_feeder", decode::<CapsuleFeederOutput>),
+            ("cup_holder", decode::<CupHolderOutput>),
+            ("Tank", decode::<TankOutput>),
+            ("pdu", decode::<PDUOutput>),
+            ("light", decode::<LightOutput>),
+        ];
+        modules
+            .iter()
+            .map(|(name, decode_fn)| {
+                let layout = layouts.get(name).expect("module layout is defined");
+                let converter = ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format());
+                (converter, *decode_fn)
+            })
+            .collect()
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    // the MQTT thread drops payloads that are not UTF-8 before decoding
+    if let Ok(frame) = std::str::from_utf8(data) {
+        for (converter, decode_fn) in converters() {
+            decode_fn(converter, frame);
+        }
+    }
+});
@@ -0,0 +1,63 @@
+#![no_main]
+
+//! checks `decode(encode(x)) == x` for every output message that decodes from an
+//! arbitrary `/get` payload, run with `cargo +nightly fuzz run output_roundtrip`
+//! from the repository root
+
+use coffee_maker_driver::module_layout::ModuleLayouts;
+use coffee_maker_driver::module_msg_converter::{ModuleMsgConverter, ModuleOutputMsg};
+use libfuzzer_sys::fuzz_target;
+use obd_coffee_maker_interface::msg::{
+    CapsuleFeederOutput, CoffeeFeederOutput, CupHolderOutput, LightOutput, PDUOutput, TankOutput,
+};
+use std::{fmt::Debug, sync::OnceLock};
+
+const MODULE_LAYOUTS: &str = include_str!("../../config/modules.toml");
+
+type RoundtripFn = fn(&ModuleMsgConverter, &str);
+
+fn roundtrip<T: ModuleOutputMsg + PartialEq + Debug>(converter: &ModuleMsgConverter, frame: &str) {
+    // skip the checksum so every frame with valid fields gets through
+    let decoded = match converter.decode_output::<T>(frame) {
+        Ok(decoded) => decoded,
+        Err(_) => return,
+    };
+    let encoded = converter
+        .encode_output(&decoded)
+        .unwrap_or_else(|e| panic!("{:?} decoded from '{}' cannot be encoded: {}", decoded, frame, e));
+    let redecoded = converter
+        .decode_get_str::<T>(&encoded)
+        .unwrap_or_else(|e| panic!("'{}' encoded from '{}' cannot be decoded: {}", encoded, frame, e));
+    assert_eq!(decoded, redecoded, "'{}' re-encoded as '{}'", frame, encoded);
+}
+
+fn converters() -> &'static Vec<(ModuleMsgConverter, RoundtripFn)> {
+    static CONVERTERS: OnceLock<Vec<(ModuleMsgConverter, RoundtripFn)>> = OnceLock::new();
+    CONVERTERS.get_or_init(|| {
+        let layouts = ModuleLayouts::from_toml(MODULE_LAYOUTS).expect("module layouts are valid");
+        let modules: [(&str, RoundtripFn); 6] = [
+            ("coffee_feeder", roundtrip::<CoffeeFeederOutput>),
+            ("capsule_feeder", roundtrip::<CapsuleFeederOutput>),
+            ("cup_holder", roundtrip::<CupHolderOutput>),
+            ("Tank", roundtrip::<TankOutput>),
+            ("pdu", roundtrip::<PDUOutput>),
+            ("light", roundtrip::<LightOutput>),
+        ];
+        modules
+            .iter()
+            .map(|(name, roundtrip_fn)| {
+                let layout = layouts.get(name).expect("module layout is defined");
+                let converter = ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format());
+                (converter, *roundtrip_fn)
+            })
+            .collect()
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frame) = std::str::from_utf8(data) {
+        for (converter, roundtrip_fn) in converters() {
+            roundtrip_fn(converter, frame);
+        }
+    }
+});
@@ -0,0 +1,147 @@
+//! feed a capture file written by the driver (`capture_file` in its config) back through
+//! the converters, no hardware or broker needed
+//!
+//!     replay <capture file> --config <driver.toml> [--publish] [--realtime]
+//!
+//! every `/get` frame is decoded and printed, `--publish` also starts the converters on a
+//! ROS node so the decoded messages are republished on `<namespace>/output`. `/set` frames
+//! are only printed, `--realtime` keeps the delays between the captured frames.
+
+use coffee_maker_driver::config::{self, DriverConfig};
+use coffee_maker_driver::converter_registry::ConverterRegistry;
+use coffee_maker_driver::interlock::InterlockGuard;
+use coffee_maker_driver::logging;
+use coffee_maker_driver::module_layout::ModuleLayouts;
+use coffee_maker_driver::module_msg_converter::ModuleMsgConverter;
+use coffee_maker_driver::module_state::ModuleStates;
+use coffee_maker_driver::transport::{read_capture, CapturedFrame, FrameDirection, FrameTransport, InMemoryTransport};
+
+use std::{collections::HashMap, env, path::PathBuf, sync::Arc, thread, time::Duration};
+use anyhow::{anyhow, bail, Error, Result};
+use rclrs::Context;
+
+const NODE_NAME: &str = "coffee_machine_replay";
+
+struct ReplayArgs {
+    capture_file: PathBuf,
+    config_file: PathBuf,
+    publish: bool,
+    realtime: bool,
+}
+
+impl ReplayArgs {
+    fn parse(args: &[String]) -> Result<Self, Error> {
+        let config_file = config::config_path_from_args(args.iter().cloned())?
+            .ok_or_else(|| anyhow!("no config file given, use '{} <path>'", config::CONFIG_ARG))?;
+        let mut capture_file = None;
+        let mut publish = false;
+        let mut realtime = false;
+        let mut rest = args.iter().skip(1).take_while(|arg| *arg != "--ros-args");
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--publish" => publish = true,
+                "--realtime" => realtime = true,
+                config::CONFIG_ARG => {
+                    rest.next();
+                }
+                arg if arg.starts_with("--") => bail!("unknown option '{}'", arg),
+                arg => match capture_file {
+                    None => capture_file = Some(PathBuf::from(arg)),
+                    Some(_) => bail!("only one capture file can be replayed, got '{}'", arg),
+                },
+            }
+        }
+        let capture_file = capture_file.ok_or_else(|| anyhow!(
+            "usage: replay <capture file> {} <driver.toml> [--publish] [--realtime]", config::CONFIG_ARG
+        ))?;
+        Ok(Self { capture_file, config_file, publish, realtime })
+    }
+}
+
+/// decoded line of one captured frame
+fn describe(decoders: &HashMap<String, ModuleMsgConverter>, captured: &CapturedFrame) -> String {
+    let prefix = format!(
+        "{}.{:06} {}/{}", captured.timestamp.as_secs(), captured.timestamp.subsec_micros(),
+        captured.namespace, captured.direction
+    );
+    if captured.direction == FrameDirection::Set {
+        return format!("{} {}", prefix, captured.frame);
+    }
+    let decoder = match decoders.get(&captured.namespace) {
+        Some(decoder) => decoder,
+        None => return format!("{} {} (unknown module)", prefix, captured.frame),
+    };
+    match decoder.decode_get_values(&captured.frame) {
+        Ok(values) => {
+            let mut line = format!("{} state={}", prefix, values.state);
+            for (target, value) in &values.fields {
+                line.push_str(&format!(" {}={}", target, value));
+            }
+            line
+        }
+        Err(e) => format!("{} {} rejected: {}", prefix, captured.frame, e),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    let replay_args = ReplayArgs::parse(&args)?;
+    let driver_config = DriverConfig::from_file(&replay_args.config_file)?;
+    let module_layouts = ModuleLayouts::from_file(&driver_config.module_layout_file)?;
+    let frames = read_capture(&replay_args.capture_file)?;
+
+    let decoders: HashMap<String, ModuleMsgConverter> = module_layouts.module
+        .iter()
+        .map(|layout| (
+            layout.name.clone(),
+            ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format()),
+        ))
+        .collect();
+
+    // the converters only run when republishing, they need a ROS node
+    let transport = Arc::new(InMemoryTransport::new());
+    let _node = if replay_args.publish {
+        let ctx = Context::new(args.clone())?;
+        let node = rclrs::create_node(&ctx, NODE_NAME)?;
+        logging::init(&driver_config.log)?.start(&node, NODE_NAME)?;
+
+        let frame_transport: Arc<dyn FrameTransport> = transport.clone();
+        let states = Arc::new(ModuleStates::new());
+        // nothing is sent to a module, every command is allowed
+        let interlock = Arc::new(InterlockGuard::unrestricted(states.clone()));
+
+        let converters = ConverterRegistry::with_modules(
+            frame_transport, node.clone(), &module_layouts, &driver_config.command, states, interlock
+        )?;
+        converters.start_all()?;
+
+        let converters = Arc::new(converters);
+        transport.start_receiving(Box::new(move |namespace, frame| {
+            // decode errors are reported by the converter and printed by `describe`
+            let _ = converters.dispatch(namespace, frame);
+        }))?;
+        Some(node)
+    } else {
+        None
+    };
+
+    let mut previous = None;
+    for captured in &frames {
+        if replay_args.realtime {
+            if let Some(delay) = previous.and_then(|previous| captured.timestamp.checked_sub(previous)) {
+                thread::sleep(delay);
+            }
+            previous = Some(captured.timestamp);
+        }
+        println!("{}", describe(&decoders, captured));
+        if replay_args.publish && captured.direction == FrameDirection::Get {
+            transport.receive_frame(&captured.namespace, &captured.frame)?;
+        }
+    }
+
+    // let the middleware deliver the last messages before the node goes away
+    if replay_args.publish {
+        thread::sleep(Duration::from_millis(500));
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! simulated coffee maker modules on the MQTT broker of a driver config, the driver
+//! runs unchanged against it
+//!
+//!     simulator --config <driver.toml>
+//!
+//! every module of the layouts answers `<namespace>/set` on `<namespace>/get` and sends
+//! its state every `simulator.period_ms`. the driver itself can run the same simulation
+//! without a broker with `transport = "simulator"`.
+
+use coffee_maker_driver::config::{self, DriverConfig};
+use coffee_maker_driver::logging;
+use coffee_maker_driver::module_layout::ModuleLayouts;
+use coffee_maker_driver::simulator::Simulator;
+
+use std::{env, sync::mpsc, thread, time::Duration};
+use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+use rumqttc::{Client, Event, Packet, QoS};
+
+fn subscribe_all(client: &mut Client, namespaces: &[String]) {
+    for namespace in namespaces {
+        // called from the event loop, which must not block on a full request channel
+        if let Err(e) = client.try_subscribe(format!("{}/set", namespace), QoS::AtLeastOnce) {
+            error!("Failed to subscribe '{}/set': {:?}", namespace, e);
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = config::config_path_from_args(env::args())?
+        .ok_or_else(|| anyhow!("usage: simulator {} <driver.toml>", config::CONFIG_ARG))?;
+    let driver_config = DriverConfig::from_file(&config_path)?;
+    logging::init(&driver_config.log)?;
+    let module_layouts = ModuleLayouts::from_file(&driver_config.module_layout_file)?;
+
+    // the driver may connect with the configured client id at the same time
+    // checked here, the driver only validates the section of its selected transport
+    let mut mqtt_config = driver_config.mqtt()?.clone();
+    mqtt_config.validate()?;
+    mqtt_config.client_id = format!("{}-simulator", mqtt_config.client_id);
+    let (client, mut connection) = Client::new(mqtt_config.mqtt_options(), mqtt_config.request_capacity);
+
+    let mut simulator = Simulator::new(&module_layouts, &driver_config.simulator);
+    let namespaces: Vec<String> = simulator.namespaces().map(str::to_string).collect();
+    let (sender, receiver) = mpsc::channel();
+
+    let mut event_client = client.clone();
+    thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::ConnAck(connack))) => {
+                    info!("connected to broker, simulating {}", namespaces.join(", "));
+                    // a clean session forgets every subscription of the previous one
+                    if !connack.session_present {
+                        subscribe_all(&mut event_client, &namespaces);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    let namespace = match publish.topic.strip_suffix("/set") {
+                        Some(namespace) => namespace.to_string(),
+                        None => {
+                            warn!("incorrect format 'name/set' mqtt topic: {}", publish.topic);
+                            continue;
+                        }
+                    };
+                    match String::from_utf8(publish.payload.to_vec()) {
+                        Ok(frame) => {
+                            if sender.send((namespace, frame)).is_err() {
+                                return;
+                            }
+                        }
+                        Err(_) => warn!("cannot convert bytes to String"),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("connection error: {}, reconnecting", e);
+                    thread::sleep(Duration::from_millis(mqtt_config.reconnect_initial_ms));
+                }
+            }
+        }
+    });
+
+    let mut client = client;
+    simulator.run(driver_config.simulator.period(), receiver, |namespace, frame| {
+        if let Err(e) = client.publish(format!("{}/get", namespace), QoS::AtLeastOnce, false, frame.as_bytes().to_vec()) {
+            error!(target: namespace, "Failed to publish simulated frame: {:?}", e);
+        }
+    });
+
+    Err(anyhow!("MQTT connection closed").into())
+}
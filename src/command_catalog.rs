@@ -0,0 +1,187 @@
+use crate::command_tracker::CommandError;
+use crate::module_layout::{CommandLayout, ModuleLayout};
+use serde::Deserialize;
+use std::fmt;
+
+/// command given in a config file, its code or its name in the command catalog of the module
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum CommandRef {
+    Code(u8),
+    Name(String),
+}
+
+/// named commands of one module, checked before a `/set` frame is encoded.
+/// a module without catalog accepts every command
+#[derive(Debug, Clone, Default)]
+pub struct CommandCatalog {
+    commands: Vec<CommandLayout>,
+}
+
+impl CommandCatalog {
+    pub fn new(layout: &ModuleLayout) -> Self {
+        let commands = layout
+            .command
+            .iter()
+            .map(|command| CommandLayout { max: command.max_value(layout), ..command.clone() })
+            .collect();
+        Self { commands }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn commands(&self) -> &[CommandLayout] {
+        &self.commands
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&CommandLayout> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    pub fn by_code(&self, code: u8) -> Option<&CommandLayout> {
+        self.commands.iter().find(|command| command.code == code)
+    }
+
+    /// code of `command`, a name must be in the catalog and so must a code once there is one
+    pub fn code(&self, command: &CommandRef) -> Option<u8> {
+        match command {
+            CommandRef::Code(code) if self.is_empty() || self.by_code(*code).is_some() => Some(*code),
+            CommandRef::Code(_) => None,
+            CommandRef::Name(name) => self.by_name(name).map(|command| command.code),
+        }
+    }
+
+    /// state the module reports once it executed `code`, see `CommandLayout::state`
+    pub fn expected_state(&self, code: u8) -> Option<u8> {
+        self.by_code(code).and_then(|command| command.state)
+    }
+
+    /// check a raw command against the catalog
+    pub fn check(&self, code: u8, value: u16) -> Result<(), CommandError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let command = self
+            .by_code(code)
+            .ok_or_else(|| CommandError::UnknownCommand { command: code.to_string() })?;
+        Self::check_range(command, value)
+    }
+
+    /// code of the command called `name`, once its value is checked
+    pub fn resolve(&self, name: &str, value: u16) -> Result<u8, CommandError> {
+        let command = self
+            .by_name(name)
+            .ok_or_else(|| CommandError::UnknownCommand { command: format!("'{}'", name) })?;
+        Self::check_range(command, value)?;
+        Ok(command.code)
+    }
+
+    fn check_range(command: &CommandLayout, value: u16) -> Result<(), CommandError> {
+        if value < command.min || value > command.max {
+            return Err(CommandError::ValueOutOfRange {
+                command: format!("'{}'", command.name),
+                value,
+                min: command.min,
+                max: command.max,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CommandRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandRef::Code(code) => write!(f, "{}", code),
+            CommandRef::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interlock::Interlocks;
+    use crate::module_layout::ModuleLayouts;
+    use crate::recipe::Recipes;
+
+    fn layouts() -> ModuleLayouts {
+        ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap()
+    }
+
+    fn light() -> CommandCatalog {
+        CommandCatalog::new(layouts().get("light").unwrap())
+    }
+
+    #[test]
+    fn resolves_names_to_codes() {
+        let catalog = light();
+        assert_eq!(catalog.code(&CommandRef::Name("on".to_string())), Some(1));
+        assert_eq!(catalog.code(&CommandRef::Code(9)), Some(9));
+        assert_eq!(catalog.resolve("off", 0), Ok(0));
+        assert_eq!(catalog.expected_state(1), Some(0));
+        assert_eq!(catalog.expected_state(9), None);
+    }
+
+    #[test]
+    fn rejects_unknown_names_and_codes() {
+        let catalog = light();
+        assert_eq!(catalog.code(&CommandRef::Name("dim".to_string())), None);
+        assert_eq!(catalog.code(&CommandRef::Code(5)), None);
+        assert_eq!(catalog.resolve("dim", 0), Err(CommandError::UnknownCommand { command: "'dim'".to_string() }));
+        assert_eq!(catalog.check(5, 0), Err(CommandError::UnknownCommand { command: "5".to_string() }));
+    }
+
+    #[test]
+    fn checks_the_value_range() {
+        let catalog = light();
+        assert_eq!(catalog.check(1, 1), Ok(()));
+        let out_of_range = CommandError::ValueOutOfRange { command: "'on'".to_string(), value: 2, min: 0, max: 1 };
+        assert_eq!(catalog.check(1, 2), Err(out_of_range.clone()));
+        assert_eq!(catalog.resolve("on", 2), Err(out_of_range));
+    }
+
+    #[test]
+    fn omitted_max_is_bounded_by_the_value_field() {
+        let layouts = layouts();
+        let layout = layouts.get("cup_holder").unwrap();
+        let catalog = CommandCatalog::new(layout);
+        let max = layout.max_value().min(u16::MAX as u64) as u16;
+        assert_eq!(catalog.by_name("move_to").map(|command| command.max), Some(max));
+        assert_eq!(catalog.check(1, max), Ok(()));
+    }
+
+    #[test]
+    fn module_without_catalog_accepts_every_command() {
+        let mut layout = layouts().get("light").unwrap().clone();
+        layout.command.clear();
+        let catalog = CommandCatalog::new(&layout);
+        assert!(catalog.is_empty());
+        assert_eq!(catalog.code(&CommandRef::Code(5)), Some(5));
+        assert_eq!(catalog.code(&CommandRef::Name("on".to_string())), None);
+        assert_eq!(catalog.check(5, 1234), Ok(()));
+    }
+
+    #[test]
+    fn config_files_reject_codes_missing_from_the_catalog() {
+        let layouts = layouts();
+        let interlock = |command: &str| format!(
+            "[[interlock]]\nname = \"light_needs_power\"\nmodule = \"light\"\ncommand = {}\n\n\
+             [[interlock.require]]\nmodule = \"pdu\"\nfield = \"light_pwr\"\ncompare = \"equal\"\nvalue = 1\n",
+            command
+        );
+        assert!(Interlocks::from_toml(&interlock("1"), &layouts).is_ok());
+        let error = format!("{:#}", Interlocks::from_toml(&interlock("5"), &layouts).unwrap_err());
+        assert!(error.contains("module 'light' has no command 5"), "{}", error);
+
+        let recipe = |command: &str| format!(
+            "[[recipe]]\nname = \"light\"\n\n[[recipe.step]]\nmodule = \"light\"\ncommand = {}\n",
+            command
+        );
+        assert!(Recipes::from_toml(&recipe("0"), &layouts).is_ok());
+        let error = format!("{:#}", Recipes::from_toml(&recipe("5"), &layouts).unwrap_err());
+        assert!(error.contains("module 'light' has no command 5"), "{}", error);
+    }
+}
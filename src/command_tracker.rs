@@ -0,0 +1,407 @@
+use crate::config::CommandConfig;
+use crate::module_msg_converter::EncodeError;
+use crate::transport::FrameTransport;
+use std::{
+    fmt,
+    sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, RecvTimeoutError}, Mutex, TryLockError},
+    time::{Duration, Instant},
+};
+
+/// longest wait for an acknowledgement before the cancel flag is checked again
+const CANCEL_POLL: Duration = Duration::from_millis(20);
+
+/// reason a `/set` command was not acknowledged by its module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// the command is not in the command catalog of the module
+    UnknownCommand { command: String },
+    /// the value is outside the range the catalog allows for the command
+    ValueOutOfRange { command: String, value: u16, min: u16, max: u16 },
+    /// an interlock rule blocks the command in the current state of the modules
+    Interlocked { rule: String, reason: String },
+    /// the command or value does not fit the `/set` frame
+    Encode(EncodeError),
+    /// an other command of the module waits for its acknowledgement
+    Busy,
+    /// the transport refused the frame
+    SendFailed { reason: String },
+    /// no `/get` frame arrived within the timeout of any attempt
+    Timeout { attempts: u32, timeout_ms: u64 },
+    /// the module answered with one of its error states
+    Rejected { state: u8 },
+    /// the module kept reporting `reported` instead of the `expected` state of the command
+    UnexpectedState { expected: u8, reported: u8 },
+    /// the caller gave up waiting for the acknowledgement
+    Canceled,
+}
+
+impl CommandError {
+    /// short name of the failure class, stable for counting and logging
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CommandError::UnknownCommand { .. } => "unknown_command",
+            CommandError::ValueOutOfRange { .. } => "value_out_of_range",
+            CommandError::Interlocked { .. } => "interlocked",
+            CommandError::Encode(e) => e.kind(),
+            CommandError::Busy => "busy",
+            CommandError::SendFailed { .. } => "send_failed",
+            CommandError::Timeout { .. } => "timeout",
+            CommandError::Rejected { .. } => "rejected",
+            CommandError::UnexpectedState { .. } => "unexpected_state",
+            CommandError::Canceled => "canceled",
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand { command } => write!(f, "command {} is not in the command catalog", command),
+            CommandError::ValueOutOfRange { command, value, min, max } => {
+                write!(f, "value {} of command {} is outside {}..={}", value, command, min, max)
+            }
+            CommandError::Interlocked { rule, reason } => write!(f, "blocked by interlock '{}': {}", rule, reason),
+            CommandError::Encode(e) => write!(f, "{}", e),
+            CommandError::Busy => write!(f, "an other command of the module is in flight"),
+            CommandError::SendFailed { reason } => write!(f, "cannot send command frame: {}", reason),
+            CommandError::Timeout { attempts, timeout_ms } => {
+                write!(f, "module did not answer {} attempt(s) within {} ms", attempts, timeout_ms)
+            }
+            CommandError::Rejected { state } => write!(f, "module answered with error state {}", state),
+            CommandError::UnexpectedState { expected, reported } => {
+                write!(f, "module reported state {} instead of {}", reported, expected)
+            }
+            CommandError::Canceled => write!(f, "command canceled before the module acknowledged it"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// match every `/set` frame of one module to the next decoded `/get` frame `T` it sends
+/// that reports the state expected after the command, commands are executed one after another
+pub struct CommandTracker<T> {
+    namespace: String,
+    timeout: Duration,
+    retries: u32,
+    /// states that fail the waiting command at once
+    error_states: Vec<u8>,
+    in_flight: Mutex<()>,
+    waiting: Mutex<Option<Waiting<T>>>,
+}
+
+/// attempt of the command in flight
+struct Waiting<T> {
+    sender: mpsc::Sender<Result<T, CommandError>>,
+    /// frames received before this instant were on their way before the `/set` frame
+    sent: Instant,
+    /// state acknowledging the command, any state but an error state when `None`
+    expected: Option<u8>,
+    /// latest state reported instead of `expected`
+    reported: Option<u8>,
+}
+
+impl<T> CommandTracker<T> {
+    pub fn new(namespace: &str, config: &CommandConfig, error_states: &[u8]) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            timeout: config.timeout(),
+            retries: config.retries,
+            error_states: error_states.to_vec(),
+            in_flight: Mutex::new(()),
+            waiting: Mutex::new(None),
+        }
+    }
+
+    /// send `frame` and block until the module answers with the `expected` state,
+    /// resending it on every timeout. returns the acknowledging frame
+    pub fn execute(&self, transport: &dyn FrameTransport, frame: &str, expected: Option<u8>) -> Result<T, CommandError> {
+        self.execute_until(transport, frame, expected, &AtomicBool::new(false))
+    }
+
+    /// `execute` that stops sending and waiting once `canceled` is set
+    pub fn execute_until(
+        &self,
+        transport: &dyn FrameTransport,
+        frame: &str,
+        expected: Option<u8>,
+        canceled: &AtomicBool,
+    ) -> Result<T, CommandError> {
+        // a poisoned lock only means an other command panicked, the tracker state stays valid
+        let _in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        self.attempt(transport, frame, expected, self.retries + 1, canceled)
+    }
+
+    /// send `frame` once without waiting for an other command, blocks for at most one timeout
+    pub fn try_execute(&self, transport: &dyn FrameTransport, frame: &str, expected: Option<u8>) -> Result<T, CommandError> {
+        let _in_flight = match self.in_flight.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Err(CommandError::Busy),
+        };
+        self.attempt(transport, frame, expected, 1, &AtomicBool::new(false))
+    }
+
+    fn attempt(
+        &self,
+        transport: &dyn FrameTransport,
+        frame: &str,
+        expected: Option<u8>,
+        attempts: u32,
+        canceled: &AtomicBool,
+    ) -> Result<T, CommandError> {
+        let mut reported = None;
+        for attempt in 1..=attempts {
+            if canceled.load(Ordering::SeqCst) {
+                self.set_waiting(None);
+                return Err(CommandError::Canceled);
+            }
+            // register before sending so a fast answer is not missed
+            let (sender, receiver) = mpsc::channel();
+            self.set_waiting(Some(Waiting { sender, sent: Instant::now(), expected, reported: None }));
+
+            if let Err(e) = transport.send_frame(&self.namespace, frame) {
+                self.set_waiting(None);
+                return Err(CommandError::SendFailed { reason: format!("{:#}", e) });
+            }
+
+            match self.wait(&receiver, canceled) {
+                Some(answer) => return answer,
+                None => {
+                    reported = self.reported_state().or(reported);
+                    log::warn!(
+                        target: &self.namespace,
+                        "command not acknowledged within {} ms (attempt {}/{})",
+                        self.timeout.as_millis(), attempt, attempts
+                    )
+                }
+            }
+        }
+
+        self.set_waiting(None);
+        match (expected, reported) {
+            (Some(expected), Some(reported)) => Err(CommandError::UnexpectedState { expected, reported }),
+            _ => Err(CommandError::Timeout { attempts, timeout_ms: self.timeout.as_millis() as u64 }),
+        }
+    }
+
+    /// answer of the current attempt, `None` once its timeout elapsed
+    fn wait(&self, receiver: &mpsc::Receiver<Result<T, CommandError>>, canceled: &AtomicBool) -> Option<Result<T, CommandError>> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if canceled.load(Ordering::SeqCst) {
+                self.set_waiting(None);
+                return Some(Err(CommandError::Canceled));
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return None;
+            }
+            match receiver.recv_timeout(left.min(CANCEL_POLL)) {
+                Ok(answer) => return Some(answer),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+
+    /// hand a decoded `/get` frame reporting `state`, received at `received`, to the waiting
+    /// command if it was sent before. an error state fails the command, an other state than
+    /// the expected one keeps it waiting. returns whether the frame answered a command
+    pub fn acknowledge(&self, reply: T, state: u8, received: Instant) -> bool {
+        let mut waiting = match self.waiting.lock() {
+            Ok(waiting) => waiting,
+            Err(_) => return false,
+        };
+        let answer = match waiting.as_mut() {
+            None => return false,
+            // the frame was decoded while the command was registered but the module
+            // sent it earlier, keep waiting for the answer
+            Some(pending) if received < pending.sent => return false,
+            Some(_) if self.error_states.contains(&state) => Err(CommandError::Rejected { state }),
+            // e.g. a periodic frame sent before the module executed the command
+            Some(pending) if pending.expected.is_some_and(|expected| expected != state) => {
+                pending.reported = Some(state);
+                return false;
+            }
+            Some(_) => Ok(reply),
+        };
+        match waiting.take() {
+            Some(pending) => pending.sender.send(answer).is_ok(),
+            None => false,
+        }
+    }
+
+    fn reported_state(&self) -> Option<u8> {
+        match self.waiting.lock() {
+            Ok(waiting) => waiting.as_ref().and_then(|waiting| waiting.reported),
+            Err(_) => None,
+        }
+    }
+
+    fn set_waiting(&self, waiting: Option<Waiting<T>>) {
+        match self.waiting.lock() {
+            Ok(mut current) => *current = waiting,
+            Err(e) => *e.into_inner() = waiting,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::memory_transport::InMemoryTransport;
+    use std::{sync::Arc, thread};
+
+    const ERROR_STATE: u8 = 8;
+
+    fn tracker(timeout_ms: u64, retries: u32) -> Arc<CommandTracker<String>> {
+        Arc::new(CommandTracker::new("Tank", &CommandConfig { timeout_ms, retries }, &[ERROR_STATE]))
+    }
+
+    /// execute a command expecting `expected` on its own thread and return once it was sent `count` times
+    fn execute_expecting(
+        tracker: &Arc<CommandTracker<String>>,
+        transport: &Arc<InMemoryTransport>,
+        count: usize,
+        expected: Option<u8>,
+    ) -> thread::JoinHandle<Result<String, CommandError>> {
+        let tracker_clone = tracker.clone();
+        let transport_clone = transport.clone();
+        let handle = thread::spawn(move || tracker_clone.execute(transport_clone.as_ref(), "@TNK0001#", expected));
+        while transport.sent_frames().len() < count {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle
+    }
+
+    fn execute(
+        tracker: &Arc<CommandTracker<String>>,
+        transport: &Arc<InMemoryTransport>,
+        count: usize,
+    ) -> thread::JoinHandle<Result<String, CommandError>> {
+        execute_expecting(tracker, transport, count, None)
+    }
+
+    #[test]
+    fn returns_the_acknowledging_frame() {
+        let tracker = tracker(5000, 0);
+        let transport = Arc::new(InMemoryTransport::new());
+        let handle = execute(&tracker, &transport, 1);
+
+        assert!(tracker.acknowledge("reply".to_string(), 0, Instant::now()));
+        assert_eq!(handle.join().unwrap(), Ok("reply".to_string()));
+        assert_eq!(transport.sent_frames(), vec![("Tank".to_string(), "@TNK0001#".to_string())]);
+    }
+
+    #[test]
+    fn ignores_frames_received_before_the_send() {
+        let tracker = tracker(5000, 0);
+        let transport = Arc::new(InMemoryTransport::new());
+        let before = Instant::now();
+        let handle = execute(&tracker, &transport, 1);
+
+        assert!(!tracker.acknowledge("stale".to_string(), 0, before));
+        assert!(tracker.acknowledge("reply".to_string(), 0, Instant::now()));
+        assert_eq!(handle.join().unwrap(), Ok("reply".to_string()));
+    }
+
+    #[test]
+    fn resends_until_the_attempts_are_used_up() {
+        let tracker = tracker(10, 2);
+        let transport = Arc::new(InMemoryTransport::new());
+        let result = tracker.execute(transport.as_ref(), "@TNK0001#", None);
+
+        assert_eq!(result, Err(CommandError::Timeout { attempts: 3, timeout_ms: 10 }));
+        assert_eq!(transport.sent_frames().len(), 3);
+        // nobody waits once the command failed
+        assert!(!tracker.acknowledge("late".to_string(), 0, Instant::now()));
+    }
+
+    #[test]
+    fn acknowledges_a_retried_command() {
+        let tracker = tracker(50, 1);
+        let transport = Arc::new(InMemoryTransport::new());
+        let handle = execute(&tracker, &transport, 2);
+
+        assert!(tracker.acknowledge("reply".to_string(), 0, Instant::now()));
+        assert_eq!(handle.join().unwrap(), Ok("reply".to_string()));
+    }
+
+    #[test]
+    fn try_execute_sends_once_and_never_queues() {
+        let busy = tracker(5000, 2);
+        let transport = Arc::new(InMemoryTransport::new());
+        let handle = execute(&busy, &transport, 1);
+
+        assert_eq!(busy.try_execute(transport.as_ref(), "@TNK0002#", None), Err(CommandError::Busy));
+        assert!(busy.acknowledge("reply".to_string(), 0, Instant::now()));
+        assert_eq!(handle.join().unwrap(), Ok("reply".to_string()));
+
+        let short = tracker(10, 2);
+        let result = short.try_execute(transport.as_ref(), "@TNK0002#", None);
+        assert_eq!(result, Err(CommandError::Timeout { attempts: 1, timeout_ms: 10 }));
+        assert_eq!(transport.sent_frames().len(), 2);
+    }
+
+    #[test]
+    fn frames_without_command_are_not_acknowledgements() {
+        let tracker = tracker(5000, 0);
+        assert!(!tracker.acknowledge("status".to_string(), 0, Instant::now()));
+    }
+
+    #[test]
+    fn periodic_frames_with_an_other_state_do_not_acknowledge() {
+        let tracker = tracker(5000, 0);
+        let transport = Arc::new(InMemoryTransport::new());
+        let handle = execute_expecting(&tracker, &transport, 1, Some(1));
+
+        // the module still reports its previous state until it executed the command
+        assert!(!tracker.acknowledge("periodic".to_string(), 0, Instant::now()));
+        assert!(tracker.acknowledge("reply".to_string(), 1, Instant::now()));
+        assert_eq!(handle.join().unwrap(), Ok("reply".to_string()));
+    }
+
+    #[test]
+    fn fails_when_the_expected_state_never_comes() {
+        let tracker = tracker(50, 1);
+        let transport = Arc::new(InMemoryTransport::new());
+        let handle = execute_expecting(&tracker, &transport, 1, Some(1));
+
+        assert!(!tracker.acknowledge("periodic".to_string(), 0, Instant::now()));
+        assert_eq!(handle.join().unwrap(), Err(CommandError::UnexpectedState { expected: 1, reported: 0 }));
+        assert_eq!(transport.sent_frames().len(), 2);
+    }
+
+    #[test]
+    fn error_state_fails_the_command_at_once() {
+        let tracker = tracker(5000, 2);
+        let transport = Arc::new(InMemoryTransport::new());
+        let handle = execute(&tracker, &transport, 1);
+
+        assert!(tracker.acknowledge("error".to_string(), ERROR_STATE, Instant::now()));
+        assert_eq!(handle.join().unwrap(), Err(CommandError::Rejected { state: ERROR_STATE }));
+        assert_eq!(transport.sent_frames().len(), 1);
+    }
+
+    #[test]
+    fn cancel_stops_waiting_for_the_acknowledgement() {
+        let tracker = tracker(5000, 2);
+        let transport = Arc::new(InMemoryTransport::new());
+        let canceled = Arc::new(AtomicBool::new(false));
+        let (tracker_clone, transport_clone, canceled_clone) = (tracker.clone(), transport.clone(), canceled.clone());
+        let handle = thread::spawn(move || {
+            tracker_clone.execute_until(transport_clone.as_ref(), "@TNK0001#", None, &canceled_clone)
+        });
+        while transport.sent_frames().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let started = Instant::now();
+        canceled.store(true, Ordering::SeqCst);
+        assert_eq!(handle.join().unwrap(), Err(CommandError::Canceled));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(transport.sent_frames().len(), 1);
+        assert!(!tracker.acknowledge("late".to_string(), 0, Instant::now()));
+    }
+}
@@ -0,0 +1,757 @@
+use anyhow::{anyhow, bail, Context, Error, Result};
+use rumqttc::MqttOptions;
+use serde::Deserialize;
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}, str::FromStr, time::Duration};
+
+/// command line flag used to point the driver at its config file
+pub const CONFIG_ARG: &str = "--config";
+/// ROS parameter used when no `--config` flag is given
+pub const CONFIG_PARAM: &str = "config_file";
+/// prefix of every environment variable that overrides a config value
+pub const ENV_PREFIX: &str = "COFFEE_MAKER_";
+
+const MQTT_ENV: &[&str] = &[
+    "MQTT_HOST", "MQTT_PORT", "MQTT_CLIENT_ID", "MQTT_USERNAME", "MQTT_PASSWORD", "MQTT_KEEP_ALIVE_SECS",
+    "MQTT_REQUEST_CAPACITY", "MQTT_RECONNECT_INITIAL_MS", "MQTT_RECONNECT_MAX_MS",
+];
+const SERIAL_ENV: &[&str] = &["SERIAL_PORT", "SERIAL_BAUD_RATE", "SERIAL_READ_TIMEOUT_MS"];
+
+///////////////////////////////////////////////////////////
+//                   Struct Defination                 ////
+///////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DriverConfig {
+    /// module frame layouts, relative paths are resolved against the config file directory
+    pub module_layout_file: PathBuf,
+    /// brew recipes, resolved like `module_layout_file`, no recipe is offered when omitted
+    #[serde(default)]
+    pub recipe_file: Option<PathBuf>,
+    /// rules checked before every `/set` command, resolved like `module_layout_file`
+    #[serde(default)]
+    pub interlock_file: Option<PathBuf>,
+    /// every raw frame is appended here for the `replay` tool, resolved like `module_layout_file`
+    #[serde(default)]
+    pub capture_file: Option<PathBuf>,
+    #[serde(default)]
+    pub transport: TransportKind,
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    #[serde(default)]
+    pub serial: Option<SerialConfig>,
+    #[serde(default)]
+    pub command: CommandConfig,
+    #[serde(default)]
+    pub diagnostics: DiagnosticsConfig,
+    #[serde(default)]
+    pub simulator: SimulatorConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+}
+
+/// link used to exchange frames with the modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    #[default]
+    Mqtt,
+    Serial,
+    /// simulated modules inside the driver process, no hardware or broker needed
+    Simulator,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "MqttConfig::default_port")]
+    pub port: u16,
+    pub client_id: String,
+    /// broker login, an empty username connects anonymously
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default = "MqttConfig::default_keep_alive_secs")]
+    pub keep_alive_secs: u64,
+    #[serde(default = "MqttConfig::default_request_capacity")]
+    pub request_capacity: usize,
+    /// first wait before reconnecting to a lost broker, doubled after every failed attempt
+    #[serde(default = "MqttConfig::default_reconnect_initial_ms")]
+    pub reconnect_initial_ms: u64,
+    /// upper bound of the reconnect wait
+    #[serde(default = "MqttConfig::default_reconnect_max_ms")]
+    pub reconnect_max_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SerialConfig {
+    /// device path, e.g. `/dev/ttyUSB0`
+    pub port: String,
+    #[serde(default = "SerialConfig::default_baud_rate")]
+    pub baud_rate: u32,
+    /// a frame cut by a pause longer than this is dropped
+    #[serde(default = "SerialConfig::default_read_timeout_ms")]
+    pub read_timeout_ms: u64,
+}
+
+/// acknowledgement of `/set` commands by the next `/get` frame of the module
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandConfig {
+    /// wait for the acknowledging `/get` frame of one attempt
+    #[serde(default = "CommandConfig::default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// attempts after the first one before a command fails
+    #[serde(default = "CommandConfig::default_retries")]
+    pub retries: u32,
+}
+
+/// `/diagnostics` publication
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiagnosticsConfig {
+    #[serde(default = "DiagnosticsConfig::default_period_ms")]
+    pub period_ms: u64,
+    /// a module without watchdog is reported offline once its latest frame is this old
+    #[serde(default = "DiagnosticsConfig::default_stale_after_ms")]
+    pub stale_after_ms: u64,
+}
+
+/// simulated modules of the `simulator` transport and binary
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulatorConfig {
+    /// every module sends its state unasked at this period
+    #[serde(default = "SimulatorConfig::default_period_ms")]
+    pub period_ms: u64,
+    /// units per second a running operation moves its field towards the goal
+    #[serde(default = "SimulatorConfig::default_speed")]
+    pub speed: u32,
+}
+
+/// log levels: `off`, `error`, `warn`, `info`, `debug` or `trace`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogConfig {
+    #[serde(default = "LogConfig::default_level")]
+    pub level: String,
+    /// level per target, a module namespace or a rust module path
+    #[serde(default)]
+    pub targets: BTreeMap<String, String>,
+}
+
+///////////////////////////////////////////////////////////
+//                   Struct Implementation             ////
+///////////////////////////////////////////////////////////
+
+impl DriverConfig {
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               construction                                             ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        let mut config: DriverConfig = toml::from_str(content)?;
+        config.apply_env_overrides()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("cannot read config file '{}'", path.display()))?;
+        let mut config: DriverConfig = toml::from_str(&content)
+            .with_context(|| format!("invalid config file '{}'", path.display()))?;
+        // only file values are relative to the config file, environment values stay
+        // relative to the working directory
+        if let Some(config_dir) = path.parent() {
+            config.resolve_paths(config_dir);
+        }
+        config.apply_env_overrides()
+            .and_then(|_| config.validate())
+            .with_context(|| format!("invalid config file '{}'", path.display()))?;
+        Ok(config)
+    }
+
+    fn resolve_paths(&mut self, config_dir: &Path) {
+        if self.module_layout_file.is_relative() {
+            self.module_layout_file = config_dir.join(&self.module_layout_file);
+        }
+        let optional_files = [&mut self.recipe_file, &mut self.interlock_file, &mut self.capture_file];
+        for file in optional_files.into_iter().flatten().filter(|file| file.is_relative()) {
+            *file = config_dir.join(&*file);
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               class functions                                          ////
+    ////////////////////////////////////////////////////////////////////////////////
+
+    /// override file values with `COFFEE_MAKER_<FIELD>` environment variables
+    pub fn apply_env_overrides(&mut self) -> Result<(), Error> {
+        if let Some(module_layout_file) = env_var("MODULE_LAYOUT_FILE") {
+            self.module_layout_file = PathBuf::from(module_layout_file);
+        }
+        if let Some(recipe_file) = env_var("RECIPE_FILE") {
+            self.recipe_file = Some(PathBuf::from(recipe_file));
+        }
+        if let Some(interlock_file) = env_var("INTERLOCK_FILE") {
+            self.interlock_file = Some(PathBuf::from(interlock_file));
+        }
+        if let Some(capture_file) = env_var("CAPTURE_FILE") {
+            self.capture_file = Some(PathBuf::from(capture_file));
+        }
+        if let Some(transport) = env_var("TRANSPORT") {
+            self.transport = match transport.as_str() {
+                "mqtt" => TransportKind::Mqtt,
+                "serial" => TransportKind::Serial,
+                "simulator" => TransportKind::Simulator,
+                _ => bail!("cannot parse environment variable {}TRANSPORT='{}'", ENV_PREFIX, transport),
+            };
+        }
+
+        // a section missing from the file is built from the environment alone,
+        // `validate` reports the required values that are still missing
+        if self.serial.is_none() && SERIAL_ENV.iter().any(|name| env_var(name).is_some()) {
+            self.serial = Some(SerialConfig::default());
+        }
+        if let Some(serial) = &mut self.serial {
+            if let Some(port) = env_var("SERIAL_PORT") {
+                serial.port = port;
+            }
+            if let Some(baud_rate) = env_var("SERIAL_BAUD_RATE") {
+                serial.baud_rate = parse_env("SERIAL_BAUD_RATE", &baud_rate)?;
+            }
+            if let Some(read_timeout) = env_var("SERIAL_READ_TIMEOUT_MS") {
+                serial.read_timeout_ms = parse_env("SERIAL_READ_TIMEOUT_MS", &read_timeout)?;
+            }
+        }
+
+        if let Some(timeout) = env_var("COMMAND_TIMEOUT_MS") {
+            self.command.timeout_ms = parse_env("COMMAND_TIMEOUT_MS", &timeout)?;
+        }
+        if let Some(retries) = env_var("COMMAND_RETRIES") {
+            self.command.retries = parse_env("COMMAND_RETRIES", &retries)?;
+        }
+        if let Some(level) = env_var("LOG_LEVEL") {
+            self.log.level = level;
+        }
+        if let Some(period) = env_var("DIAGNOSTICS_PERIOD_MS") {
+            self.diagnostics.period_ms = parse_env("DIAGNOSTICS_PERIOD_MS", &period)?;
+        }
+        if let Some(stale_after) = env_var("DIAGNOSTICS_STALE_AFTER_MS") {
+            self.diagnostics.stale_after_ms = parse_env("DIAGNOSTICS_STALE_AFTER_MS", &stale_after)?;
+        }
+        if let Some(period) = env_var("SIMULATOR_PERIOD_MS") {
+            self.simulator.period_ms = parse_env("SIMULATOR_PERIOD_MS", &period)?;
+        }
+        if let Some(speed) = env_var("SIMULATOR_SPEED") {
+            self.simulator.speed = parse_env("SIMULATOR_SPEED", &speed)?;
+        }
+
+        if self.mqtt.is_none() && MQTT_ENV.iter().any(|name| env_var(name).is_some()) {
+            self.mqtt = Some(MqttConfig::default());
+        }
+        let mqtt = match &mut self.mqtt {
+            Some(mqtt) => mqtt,
+            None => return Ok(()),
+        };
+        if let Some(host) = env_var("MQTT_HOST") {
+            mqtt.host = host;
+        }
+        if let Some(port) = env_var("MQTT_PORT") {
+            mqtt.port = parse_env("MQTT_PORT", &port)?;
+        }
+        if let Some(client_id) = env_var("MQTT_CLIENT_ID") {
+            mqtt.client_id = client_id;
+        }
+        if let Some(username) = env_var("MQTT_USERNAME") {
+            mqtt.username = Some(username);
+        }
+        if let Some(password) = env_var("MQTT_PASSWORD") {
+            mqtt.password = Some(password);
+        }
+        if let Some(keep_alive) = env_var("MQTT_KEEP_ALIVE_SECS") {
+            mqtt.keep_alive_secs = parse_env("MQTT_KEEP_ALIVE_SECS", &keep_alive)?;
+        }
+        if let Some(capacity) = env_var("MQTT_REQUEST_CAPACITY") {
+            mqtt.request_capacity = parse_env("MQTT_REQUEST_CAPACITY", &capacity)?;
+        }
+        if let Some(initial) = env_var("MQTT_RECONNECT_INITIAL_MS") {
+            mqtt.reconnect_initial_ms = parse_env("MQTT_RECONNECT_INITIAL_MS", &initial)?;
+        }
+        if let Some(max) = env_var("MQTT_RECONNECT_MAX_MS") {
+            mqtt.reconnect_max_ms = parse_env("MQTT_RECONNECT_MAX_MS", &max)?;
+        }
+        Ok(())
+    }
+
+    /// sections of the transports that are not selected are left unchecked, they may
+    /// hold nothing but stray environment overrides
+    pub fn validate(&self) -> Result<(), Error> {
+        self.command.validate()?;
+        self.diagnostics.validate()?;
+        self.simulator.validate()?;
+        self.log.validate()?;
+        match self.transport {
+            TransportKind::Mqtt => self.mqtt()?.validate(),
+            TransportKind::Serial => self.serial()?.validate(),
+            TransportKind::Simulator => Ok(()),
+        }
+    }
+
+    pub fn mqtt(&self) -> Result<&MqttConfig, Error> {
+        self.mqtt.as_ref().ok_or_else(|| anyhow!("[mqtt] section is missing"))
+    }
+
+    pub fn serial(&self) -> Result<&SerialConfig, Error> {
+        self.serial.as_ref().ok_or_else(|| anyhow!("[serial] section is missing"))
+    }
+}
+
+/// section without host and client id, only used to collect environment overrides
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: Self::default_port(),
+            client_id: String::new(),
+            username: None,
+            password: None,
+            keep_alive_secs: Self::default_keep_alive_secs(),
+            request_capacity: Self::default_request_capacity(),
+            reconnect_initial_ms: Self::default_reconnect_initial_ms(),
+            reconnect_max_ms: Self::default_reconnect_max_ms(),
+        }
+    }
+}
+
+impl MqttConfig {
+    fn default_port() -> u16 {
+        1883
+    }
+
+    fn default_keep_alive_secs() -> u64 {
+        5
+    }
+
+    fn default_request_capacity() -> usize {
+        10
+    }
+
+    fn default_reconnect_initial_ms() -> u64 {
+        500
+    }
+
+    fn default_reconnect_max_ms() -> u64 {
+        30_000
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.host.trim().is_empty() {
+            bail!("mqtt.host must not be empty");
+        }
+        if self.client_id.trim().is_empty() {
+            bail!("mqtt.client_id must not be empty");
+        }
+        if self.port == 0 {
+            bail!("mqtt.port must be in range 1..=65535");
+        }
+        // rumqttc rejects keep alive below 5 seconds
+        if self.keep_alive_secs < 5 {
+            bail!("mqtt.keep_alive_secs must be at least 5 but is {}", self.keep_alive_secs);
+        }
+        if self.request_capacity == 0 {
+            bail!("mqtt.request_capacity must be greater than 0");
+        }
+        if self.password.as_deref().is_some_and(|password| !password.is_empty()) && self.username().is_none() {
+            bail!("mqtt.password is set but mqtt.username is missing");
+        }
+        if self.reconnect_initial_ms == 0 {
+            bail!("mqtt.reconnect_initial_ms must be greater than 0");
+        }
+        if self.reconnect_max_ms < self.reconnect_initial_ms {
+            bail!(
+                "mqtt.reconnect_max_ms ({}) must not be below mqtt.reconnect_initial_ms ({})",
+                self.reconnect_max_ms, self.reconnect_initial_ms
+            );
+        }
+        Ok(())
+    }
+
+    pub fn mqtt_options(&self) -> MqttOptions {
+        let mut mqtt_options = MqttOptions::new(self.client_id.clone(), self.host.clone(), self.port);
+        mqtt_options.set_keep_alive(Duration::from_secs(self.keep_alive_secs));
+        if let Some(username) = self.username() {
+            mqtt_options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+        mqtt_options
+    }
+
+    fn username(&self) -> Option<&str> {
+        self.username.as_deref().filter(|username| !username.is_empty())
+    }
+}
+
+/// section without port, only used to collect environment overrides
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            port: String::new(),
+            baud_rate: Self::default_baud_rate(),
+            read_timeout_ms: Self::default_read_timeout_ms(),
+        }
+    }
+}
+
+impl SerialConfig {
+    fn default_baud_rate() -> u32 {
+        115200
+    }
+
+    fn default_read_timeout_ms() -> u64 {
+        100
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.port.trim().is_empty() {
+            bail!("serial.port must not be empty");
+        }
+        if self.baud_rate == 0 {
+            bail!("serial.baud_rate must be greater than 0");
+        }
+        if self.read_timeout_ms == 0 {
+            bail!("serial.read_timeout_ms must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: Self::default_timeout_ms(),
+            retries: Self::default_retries(),
+        }
+    }
+}
+
+impl CommandConfig {
+    fn default_timeout_ms() -> u64 {
+        1000
+    }
+
+    fn default_retries() -> u32 {
+        2
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.timeout_ms == 0 {
+            bail!("command.timeout_ms must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self { period_ms: Self::default_period_ms(), stale_after_ms: Self::default_stale_after_ms() }
+    }
+}
+
+impl DiagnosticsConfig {
+    fn default_period_ms() -> u64 {
+        1000
+    }
+
+    fn default_stale_after_ms() -> u64 {
+        5000
+    }
+
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period_ms)
+    }
+
+    pub fn stale_after(&self) -> Duration {
+        Duration::from_millis(self.stale_after_ms)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.period_ms == 0 {
+            bail!("diagnostics.period_ms must be greater than 0");
+        }
+        if self.stale_after_ms == 0 {
+            bail!("diagnostics.stale_after_ms must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self { period_ms: Self::default_period_ms(), speed: Self::default_speed() }
+    }
+}
+
+impl SimulatorConfig {
+    fn default_period_ms() -> u64 {
+        1000
+    }
+
+    fn default_speed() -> u32 {
+        500
+    }
+
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period_ms)
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.period_ms == 0 {
+            bail!("simulator.period_ms must be greater than 0");
+        }
+        if self.speed == 0 {
+            bail!("simulator.speed must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { level: Self::default_level(), targets: BTreeMap::new() }
+    }
+}
+
+impl LogConfig {
+    fn default_level() -> String {
+        "info".to_string()
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let levels = std::iter::once(("log.level".to_string(), &self.level))
+            .chain(self.targets.iter().map(|(target, level)| (format!("log.targets.{}", target), level)));
+        for (name, level) in levels {
+            if log::LevelFilter::from_str(level).is_err() {
+                bail!("{} '{}' must be off, error, warn, info, debug or trace", name, level);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// find the value of `--config <path>` or `--config=<path>` among the process arguments,
+/// ignoring everything after `--ros-args`
+pub fn config_path_from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<PathBuf>, Error> {
+    let mut args = args.into_iter().take_while(|arg| arg != "--ros-args");
+    while let Some(arg) = args.next() {
+        if arg == CONFIG_ARG {
+            return match args.next() {
+                Some(path) => Ok(Some(PathBuf::from(path))),
+                None => Err(anyhow!("{} expects a file path", CONFIG_ARG)),
+            };
+        }
+        if let Some(path) = arg.strip_prefix(&format!("{}=", CONFIG_ARG)) {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    Ok(None)
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(format!("{}{}", ENV_PREFIX, name)).ok()
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse::<T>()
+        .map_err(|_| anyhow!("cannot parse environment variable {}{}='{}'", ENV_PREFIX, name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// every test reading the environment holds this lock, the variables are process wide
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// `COFFEE_MAKER_*` variables of one test, removed again when dropped
+    struct EnvVars {
+        names: Vec<String>,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl Drop for EnvVars {
+        fn drop(&mut self) {
+            for name in &self.names {
+                env::remove_var(name);
+            }
+        }
+    }
+
+    fn env_vars(vars: &[(&str, &str)]) -> EnvVars {
+        let lock = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let names = vars
+            .iter()
+            .map(|(name, value)| {
+                let name = format!("{}{}", ENV_PREFIX, name);
+                env::set_var(&name, value);
+                name
+            })
+            .collect();
+        EnvVars { names, _lock: lock }
+    }
+
+    fn error_of(content: &str) -> String {
+        match DriverConfig::from_toml(content) {
+            Ok(_) => panic!("config was accepted"),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    const MQTT: &str = r#"
+        module_layout_file = "modules.toml"
+
+        [mqtt]
+        host = "localhost"
+        client_id = "driver"
+    "#;
+
+    #[test]
+    fn parses_the_shipped_config() {
+        let _env = env_vars(&[]);
+        let config = DriverConfig::from_toml(include_str!("../config/driver.toml")).unwrap();
+        assert_eq!(config.transport, TransportKind::Mqtt);
+        assert_eq!(config.interlock_file, Some(PathBuf::from("interlocks.toml")));
+        assert_eq!(config.recipe_file, None);
+        let mqtt = config.mqtt().unwrap();
+        assert_eq!((mqtt.host.as_str(), mqtt.port), ("192.168.1.101", 1883));
+        // the shipped placeholders connect anonymously
+        assert_eq!(mqtt.mqtt_options().credentials(), None);
+        assert_eq!((config.command.timeout_ms, config.command.retries), (1000, 2));
+        assert_eq!(config.simulator.speed, 500);
+        assert!(config.serial.is_none());
+    }
+
+    #[test]
+    fn omitted_values_take_their_defaults() {
+        let _env = env_vars(&[]);
+        let config = DriverConfig::from_toml(MQTT).unwrap();
+        let mqtt = config.mqtt().unwrap();
+        assert_eq!((mqtt.port, mqtt.keep_alive_secs, mqtt.request_capacity), (1883, 5, 10));
+        assert_eq!((config.command.timeout_ms, config.command.retries), (1000, 2));
+        assert_eq!((config.diagnostics.period_ms, config.diagnostics.stale_after_ms), (1000, 5000));
+        assert_eq!(config.log.level, "info");
+        assert!(error_of(&format!("{}\nunknown = 1", MQTT)).contains("unknown field"));
+    }
+
+    #[test]
+    fn environment_overrides_file_values() {
+        let _env = env_vars(&[
+            ("MQTT_HOST", "10.0.0.5"),
+            ("MQTT_USERNAME", "driver"),
+            ("MQTT_PASSWORD", "secret"),
+            ("COMMAND_RETRIES", "5"),
+            ("LOG_LEVEL", "debug"),
+        ]);
+        let config = DriverConfig::from_toml(MQTT).unwrap();
+        let mqtt = config.mqtt().unwrap();
+        assert_eq!(mqtt.host, "10.0.0.5");
+        assert_eq!(mqtt.mqtt_options().credentials(), Some(("driver".to_string(), "secret".to_string())));
+        assert_eq!(config.command.retries, 5);
+        assert_eq!(config.log.level, "debug");
+    }
+
+    #[test]
+    fn environment_builds_a_missing_section() {
+        let _env = env_vars(&[("TRANSPORT", "serial"), ("SERIAL_PORT", "/dev/ttyUSB1")]);
+        let config = DriverConfig::from_toml(r#"module_layout_file = "modules.toml""#).unwrap();
+        assert_eq!(config.transport, TransportKind::Serial);
+        let serial = config.serial().unwrap();
+        assert_eq!((serial.port.as_str(), serial.baud_rate), ("/dev/ttyUSB1", 115200));
+    }
+
+    #[test]
+    fn rejects_unparsable_environment_values() {
+        let _env = env_vars(&[("COMMAND_TIMEOUT_MS", "soon")]);
+        assert!(error_of(MQTT).contains("COFFEE_MAKER_COMMAND_TIMEOUT_MS='soon'"));
+        drop(_env);
+        let _env = env_vars(&[("TRANSPORT", "can")]);
+        assert!(error_of(MQTT).contains("COFFEE_MAKER_TRANSPORT='can'"));
+    }
+
+    #[test]
+    fn file_paths_are_relative_to_the_config_file_and_environment_paths_are_not() {
+        let dir = env::temp_dir().join(format!("coffee_maker_config_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("driver.toml");
+        fs::write(
+            &path,
+            format!("recipe_file = \"/etc/coffee/recipes.toml\"\ncapture_file = \"frames.capture\"\n{}", MQTT),
+        ).unwrap();
+
+        let env = env_vars(&[("INTERLOCK_FILE", "rules/interlocks.toml")]);
+        let config = DriverConfig::from_file(&path);
+        drop(env);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.module_layout_file, dir.join("modules.toml"));
+        assert_eq!(config.capture_file, Some(dir.join("frames.capture")));
+        assert_eq!(config.recipe_file, Some(PathBuf::from("/etc/coffee/recipes.toml")));
+        assert_eq!(config.interlock_file, Some(PathBuf::from("rules/interlocks.toml")));
+    }
+
+    #[test]
+    fn resolve_paths_keeps_absolute_paths() {
+        let _env = env_vars(&[]);
+        let mut config = DriverConfig::from_toml(&format!("interlock_file = \"/rules.toml\"\n{}", MQTT)).unwrap();
+        config.resolve_paths(Path::new("/opt/driver"));
+        assert_eq!(config.module_layout_file, PathBuf::from("/opt/driver/modules.toml"));
+        assert_eq!(config.interlock_file, Some(PathBuf::from("/rules.toml")));
+        assert_eq!((config.recipe_file, config.capture_file), (None, None));
+    }
+
+    #[test]
+    fn finds_the_config_path_among_the_arguments() {
+        let args = |args: &[&str]| config_path_from_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&["driver", "--config", "a.toml"]).unwrap(), Some(PathBuf::from("a.toml")));
+        assert_eq!(args(&["driver", "--config=b.toml"]).unwrap(), Some(PathBuf::from("b.toml")));
+        assert_eq!(args(&["driver"]).unwrap(), None);
+        // arguments of ROS are not ours
+        assert_eq!(args(&["driver", "--ros-args", "--config", "c.toml"]).unwrap(), None);
+        assert!(args(&["driver", "--config"]).is_err());
+    }
+
+    #[test]
+    fn only_the_selected_transport_is_validated() {
+        let _env = env_vars(&[]);
+        let empty_host = MQTT.replace(r#"host = "localhost""#, r#"host = """#);
+        assert!(error_of(&empty_host).contains("mqtt.host must not be empty"));
+        assert!(DriverConfig::from_toml(&format!("transport = \"simulator\"\n{}", empty_host)).is_ok());
+
+        let serial = r#"
+            module_layout_file = "modules.toml"
+            transport = "serial"
+        "#;
+        assert!(error_of(serial).contains("[serial] section is missing"));
+        assert!(error_of(&format!("{}\n[serial]\nport = \"\"", serial)).contains("serial.port must not be empty"));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let _env = env_vars(&[]);
+        let anonymous_password = format!("{}password = \"secret\"", MQTT);
+        assert!(error_of(&anonymous_password).contains("mqtt.username is missing"));
+        assert!(error_of(&format!("{}\n[command]\ntimeout_ms = 0", MQTT)).contains("command.timeout_ms"));
+        assert!(error_of(&format!("{}\n[log]\nlevel = \"loud\"", MQTT)).contains("log.level 'loud'"));
+        assert!(error_of(&format!("{}\n[simulator]\nspeed = 0", MQTT)).contains("simulator.speed"));
+    }
+}
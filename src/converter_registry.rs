@@ -0,0 +1,231 @@
+use anyhow::{anyhow, Context, Error, Result};
+use std::{collections::HashMap, sync::{atomic::AtomicBool, Arc}};
+
+use crate::command_tracker::CommandError;
+use crate::config::CommandConfig;
+use crate::interlock::InterlockGuard;
+use crate::module_layout::{ModuleLayout, ModuleLayouts, OperationLayout};
+use crate::module_msg_converter::{
+    CapsuleFeederConverter, CoffeeFeederConverter, Converter, CupHolderConverter, DecodeError, LightConverter,
+    ModuleConverter, PDUConverter, TankConverter,
+};
+use crate::module_operation::OperationError;
+use crate::module_state::ModuleStates;
+use crate::transport::FrameTransport;
+use rclrs::Node;
+use rosidl_runtime_rs::{Message, Service as ServiceType};
+
+/// builds the converter of a module from its layout, see `MODULE_CONVERTERS`
+type ConverterFactory = fn(&ModuleLayout, &ConverterParts) -> Arc<dyn ModuleHandler>;
+
+/// converter type of every module name a layout file may use
+const MODULE_CONVERTERS: [(&str, ConverterFactory); 6] = [
+    ("coffee_feeder", build::<CoffeeFeederConverter>),
+    ("capsule_feeder", build::<CapsuleFeederConverter>),
+    ("cup_holder", build::<CupHolderConverter>),
+    ("Tank", build::<TankConverter>),
+    ("pdu", build::<PDUConverter>),
+    ("light", build::<LightConverter>),
+];
+
+/// everything a converter shares with the other converters
+struct ConverterParts<'a> {
+    transport: Arc<dyn FrameTransport>,
+    node: Arc<Node>,
+    command_config: &'a CommandConfig,
+    states: Arc<ModuleStates>,
+    interlock: Arc<InterlockGuard>,
+}
+
+/// converter that can be built from a layout
+trait FromLayout: ModuleHandler + Sized + 'static {
+    fn from_layout(layout: &ModuleLayout, parts: &ConverterParts) -> Self;
+}
+
+impl<I: Message, O: Message, S: ServiceType, G: ServiceType> FromLayout for ModuleConverter<I, O, S, G>
+where
+    Self: ModuleHandler + 'static,
+{
+    fn from_layout(layout: &ModuleLayout, parts: &ConverterParts) -> Self {
+        Self::new(
+            parts.transport.clone(),
+            parts.node.clone(),
+            layout,
+            parts.command_config,
+            parts.states.clone(),
+            parts.interlock.clone(),
+        )
+    }
+}
+
+fn build<C: FromLayout>(layout: &ModuleLayout, parts: &ConverterParts) -> Arc<dyn ModuleHandler> {
+    Arc::new(C::from_layout(layout, parts))
+}
+
+/// object safe view of a converter, used to dispatch frames without knowing its message types
+pub trait ModuleHandler: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn start(&self) -> Result<(), Error>;
+
+    fn handle_frame(&self, frame: &str) -> Result<(), DecodeError>;
+
+    /// send a command and wait for its acknowledgement until `canceled` is set
+    fn execute_command(&self, command: u8, value: u16, canceled: &AtomicBool) -> Result<(), CommandError>;
+
+    fn run_operation(&self, operation: &OperationLayout, goal: u16, canceled: &AtomicBool) -> Result<(), OperationError>;
+
+    fn cancel_operation(&self, operation: &str) -> bool;
+}
+
+impl<C> ModuleHandler for C
+where
+    C: Converter + Send + Sync,
+{
+    fn name(&self) -> &str {
+        Converter::name(self)
+    }
+
+    fn start(&self) -> Result<(), Error> {
+        Converter::start(self)
+    }
+
+    fn handle_frame(&self, frame: &str) -> Result<(), DecodeError> {
+        Converter::handle_frame(self, frame)
+    }
+
+    fn execute_command(&self, command: u8, value: u16, canceled: &AtomicBool) -> Result<(), CommandError> {
+        Converter::execute_command_until(self, command, value, canceled).map(|_| ())
+    }
+
+    fn run_operation(&self, operation: &OperationLayout, goal: u16, canceled: &AtomicBool) -> Result<(), OperationError> {
+        Converter::run_operation(self, operation, goal, canceled).map(|_| ())
+    }
+
+    fn cancel_operation(&self, operation: &str) -> bool {
+        Converter::cancel_operation(self, operation)
+    }
+}
+
+/// converters of all modules keyed by their namespace
+#[derive(Default, Clone)]
+pub struct ConverterRegistry {
+    converters: HashMap<String, Arc<dyn ModuleHandler>>,
+}
+
+impl ConverterRegistry {
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               construction                                             ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// registry with the converter of every module of `module_layouts`, not started yet.
+    /// a module name without converter type is an error
+    pub fn with_modules(
+        transport: Arc<dyn FrameTransport>,
+        node: Arc<Node>,
+        module_layouts: &ModuleLayouts,
+        command_config: &CommandConfig,
+        states: Arc<ModuleStates>,
+        interlock: Arc<InterlockGuard>,
+    ) -> Result<Self, Error> {
+        let parts = ConverterParts { transport, node, command_config, states, interlock };
+        let mut converters = Self::new();
+        for layout in &module_layouts.module {
+            let (_, build) = MODULE_CONVERTERS
+                .iter()
+                .find(|(name, _)| *name == layout.name)
+                .ok_or_else(|| {
+                    let known: Vec<&str> = MODULE_CONVERTERS.iter().map(|(name, _)| *name).collect();
+                    anyhow!("no converter for module '{}', known modules are {}", layout.name, known.join(", "))
+                })?;
+            converters
+                .register_arc(build(layout, &parts))
+                .with_context(|| format!("[{}] cannot register converter", layout.name))?;
+        }
+        Ok(converters)
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               class functions                                          ////
+    ////////////////////////////////////////////////////////////////////////////////
+
+    /// register `converter` under its own name, a namespace can only be registered once
+    pub fn register<C: ModuleHandler + 'static>(&mut self, converter: C) -> Result<(), Error> {
+        self.register_arc(Arc::new(converter))
+    }
+
+    pub fn register_arc(&mut self, converter: Arc<dyn ModuleHandler>) -> Result<(), Error> {
+        let namespace = converter.name().to_string();
+        if self.converters.contains_key(&namespace) {
+            return Err(anyhow!("converter for namespace '{}' is already registered", namespace));
+        }
+        self.converters.insert(namespace, converter);
+        Ok(())
+    }
+
+    pub fn get(&self, namespace: &str) -> Option<Arc<dyn ModuleHandler>> {
+        self.converters.get(namespace).cloned()
+    }
+
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        self.converters.keys().map(String::as_str)
+    }
+
+    pub fn start_all(&self) -> Result<(), Error> {
+        for converter in self.converters.values() {
+            converter.start()?;
+        }
+        Ok(())
+    }
+
+    /// route an incoming frame to the converter of its namespace
+    pub fn dispatch(&self, namespace: &str, frame: &str) -> Result<(), DecodeError> {
+        if let Some(converter) = self.converters.get(namespace) {
+            return converter.handle_frame(frame);
+        } else {
+            log::warn!("no converter registered for namespace: {}", namespace);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::InMemoryTransport;
+
+    fn with_modules(module_layouts: &ModuleLayouts) -> Result<ConverterRegistry, Error> {
+        let context = rclrs::Context::new([]).unwrap();
+        let node = rclrs::create_node(&context, "converter_registry_test").unwrap();
+        let states = Arc::new(ModuleStates::new());
+        let interlock = Arc::new(InterlockGuard::unrestricted(states.clone()));
+        ConverterRegistry::with_modules(
+            Arc::new(InMemoryTransport::new()), node, module_layouts, &CommandConfig::default(), states, interlock
+        )
+    }
+
+    #[test]
+    fn registers_a_converter_per_layout() {
+        let mut module_layouts = ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap();
+        let converters = with_modules(&module_layouts).unwrap();
+        let mut namespaces: Vec<&str> = converters.namespaces().collect();
+        namespaces.sort();
+        assert_eq!(namespaces, vec!["Tank", "capsule_feeder", "coffee_feeder", "cup_holder", "light", "pdu"]);
+
+        // modules missing from the layouts get no converter
+        module_layouts.module.retain(|layout| layout.name == "light");
+        let converters = with_modules(&module_layouts).unwrap();
+        assert_eq!(converters.namespaces().collect::<Vec<_>>(), vec!["light"]);
+    }
+
+    #[test]
+    fn rejects_a_module_without_converter_type() {
+        let mut module_layouts = ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap();
+        module_layouts.module[0].name = "grinder".to_string();
+        let error = with_modules(&module_layouts).err().unwrap().to_string();
+        assert!(error.starts_with("no converter for module 'grinder', known modules are coffee_feeder"), "{}", error);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::config::DiagnosticsConfig;
+use crate::module_layout::{ModuleLayout, ModuleLayouts};
+use crate::module_state::{time_msg, ModuleSnapshot, ModuleStates};
+use crate::transport::FrameTransport;
+use std::{collections::HashMap, sync::{Arc, Mutex}, thread, time::{Duration, SystemTime}};
+use anyhow::{anyhow, Error, Result};
+use rclrs::{Node, Publisher};
+use diagnostic_msgs::msg::{DiagnosticArray, DiagnosticStatus, KeyValue};
+use std_msgs::msg::Header;
+
+/// prefix of every status name, the aggregator groups statuses by it
+const STATUS_PREFIX: &str = "coffee_maker_driver";
+
+/// link to the modules as shown on `/diagnostics`
+pub struct LinkInfo {
+    /// e.g. `mqtt`
+    pub name: String,
+    /// e.g. the broker address or the serial device
+    pub hardware_id: String,
+}
+
+/// publishes one `/diagnostics` status per module and one for the link
+pub struct DiagnosticsPublisher {
+    period: Duration,
+    /// age of the latest frame that flags a module without watchdog offline
+    stale_after: Duration,
+    modules: Vec<ModuleLayout>,
+    states: Arc<ModuleStates>,
+    transport: Arc<dyn FrameTransport>,
+    link: LinkInfo,
+    /// rejected frames of every module at the previous publication
+    reported_rejected: Mutex<HashMap<String, u64>>,
+    pub publisher: Arc<Publisher<DiagnosticArray>>,
+}
+
+impl DiagnosticsPublisher {
+    pub fn start(
+        node: &Arc<Node>,
+        config: &DiagnosticsConfig,
+        module_layouts: &ModuleLayouts,
+        states: Arc<ModuleStates>,
+        transport: Arc<dyn FrameTransport>,
+        link: LinkInfo,
+    ) -> Result<Arc<Self>, Error> {
+        let diagnostics = Arc::new(Self::new(node, config, module_layouts, states, transport, link)?);
+
+        let diagnostics_clone = diagnostics.clone();
+        thread::Builder::new()
+            .name("diagnostics".to_string())
+            .spawn(move || loop {
+                let array = diagnostics_clone.collect();
+                if let Err(e) = diagnostics_clone.publisher.publish(array) {
+                    log::error!("Failed to publish diagnostics: {:?}", e);
+                }
+                thread::sleep(diagnostics_clone.period);
+            })
+            .map_err(|e| anyhow!("cannot start diagnostics: {}", e))?;
+        Ok(diagnostics)
+    }
+
+    fn new(
+        node: &Arc<Node>,
+        config: &DiagnosticsConfig,
+        module_layouts: &ModuleLayouts,
+        states: Arc<ModuleStates>,
+        transport: Arc<dyn FrameTransport>,
+        link: LinkInfo,
+    ) -> Result<Self, Error> {
+        let publisher = node.create_publisher::<DiagnosticArray>("/diagnostics", rclrs::QOS_PROFILE_DEFAULT)?;
+        Ok(Self {
+            period: config.period(),
+            stale_after: config.stale_after(),
+            modules: module_layouts.module.clone(),
+            states,
+            transport,
+            link,
+            reported_rejected: Mutex::new(HashMap::new()),
+            publisher,
+        })
+    }
+
+    /// current status of the link and of every module, rejected frames are reported
+    /// as warning once
+    pub fn collect(&self) -> DiagnosticArray {
+        let mut status = vec![self.link_status()];
+        status.extend(self.modules.iter().map(|layout| self.module_status(layout)));
+        DiagnosticArray {
+            header: Header { stamp: time_msg(SystemTime::now()), frame_id: String::new() },
+            status,
+        }
+    }
+
+    fn link_status(&self) -> DiagnosticStatus {
+        let connected = self.transport.is_connected();
+        DiagnosticStatus {
+            level: if connected { DiagnosticStatus::OK } else { DiagnosticStatus::ERROR },
+            name: format!("{}: {} link", STATUS_PREFIX, self.link.name),
+            message: if connected { "connected" } else { "disconnected" }.to_string(),
+            hardware_id: self.link.hardware_id.clone(),
+            values: vec![key_value("connected", connected)],
+        }
+    }
+
+    fn module_status(&self, layout: &ModuleLayout) -> DiagnosticStatus {
+        let stats = self.states.stats(&layout.name);
+        let rejected = stats.rejected();
+        let newly_rejected = match self.reported_rejected.lock() {
+            Ok(mut reported) => rejected - reported.insert(layout.name.clone(), rejected).unwrap_or(0),
+            Err(_) => 0,
+        };
+
+        let mut values = vec![
+            key_value("frames_decoded", stats.decoded),
+            key_value("frames_rejected", rejected),
+            key_value("checksum_error_rate", format!("{:.4}", stats.error_rate("checksum_mismatch"))),
+        ];
+        values.extend(stats.errors.iter().map(|(kind, count)| key_value(&format!("errors.{}", kind), count)));
+
+        let snapshot = self.states.get(&layout.name);
+        let age = snapshot.as_ref().map(ModuleSnapshot::age);
+        if let (Some(snapshot), Some(age)) = (&snapshot, age) {
+            values.push(key_value("last_frame_age_ms", age.as_millis()));
+            values.push(key_value("state", snapshot.values.state));
+        }
+        let timeout = layout.watchdog.as_ref().map_or(self.stale_after, |watchdog| watchdog.timeout());
+        let stale = age.is_some_and(|age| age >= timeout);
+        values.push(key_value("connected", age.is_some() && !stale));
+
+        let (level, message) = match age {
+            None if rejected > 0 => (DiagnosticStatus::ERROR, format!("no frame decoded, {} rejected", rejected)),
+            None => (DiagnosticStatus::STALE, "no frame received".to_string()),
+            Some(age) if stale => (DiagnosticStatus::ERROR, format!("offline, no frame for {} ms", age.as_millis())),
+            Some(_) if newly_rejected > 0 => {
+                (DiagnosticStatus::WARN, format!("{} frame(s) rejected since the last report", newly_rejected))
+            }
+            Some(_) => (DiagnosticStatus::OK, "online".to_string()),
+        };
+
+        DiagnosticStatus {
+            level,
+            name: format!("{}: {}", STATUS_PREFIX, layout.name),
+            message,
+            hardware_id: layout.input.header.content(),
+            values,
+        }
+    }
+}
+
+fn key_value<T: ToString>(key: &str, value: T) -> KeyValue {
+    KeyValue { key: key.to_string(), value: value.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module_layout::WatchdogLayout;
+    use crate::module_msg_converter::{DecodeError, DecodedValues};
+    use crate::transport::FrameHandler;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const STALE_AFTER_MS: u64 = 30;
+
+    /// link whose state is switched by the test
+    #[derive(Default)]
+    struct Link(AtomicBool);
+
+    impl FrameTransport for Link {
+        fn send_frame(&self, _namespace: &str, _frame: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn subscribe(&self, _namespace: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn start_receiving(&self, _handler: FrameHandler) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    /// diagnostics of the light, with a watchdog of `watchdog_ms` when given
+    fn diagnostics(watchdog_ms: Option<u64>) -> (DiagnosticsPublisher, Arc<ModuleStates>, Arc<Link>) {
+        let mut module_layouts = ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap();
+        module_layouts.module.retain(|layout| layout.name == "light");
+        module_layouts.module[0].watchdog = watchdog_ms
+            .map(|timeout_ms| WatchdogLayout { timeout_ms, poll_command: None, poll_value: 0 });
+        let context = rclrs::Context::new([]).unwrap();
+        let node = rclrs::create_node(&context, "diagnostics_test").unwrap();
+        let config = DiagnosticsConfig { period_ms: 1000, stale_after_ms: STALE_AFTER_MS };
+        let states = Arc::new(ModuleStates::new());
+        let link = Arc::new(Link::default());
+        let info = LinkInfo { name: "mqtt".to_string(), hardware_id: "localhost:1883".to_string() };
+        let diagnostics = DiagnosticsPublisher::new(&node, &config, &module_layouts, states.clone(), link.clone(), info).unwrap();
+        (diagnostics, states, link)
+    }
+
+    fn frame_received(states: &ModuleStates) {
+        states.update("light", DecodedValues { state: 1, fields: Vec::new() });
+    }
+
+    fn frame_rejected(states: &ModuleStates) {
+        states.record_error("light", &DecodeError::ChecksumMismatch { received: "00".to_string(), calculated: "4F".to_string() });
+    }
+
+    /// level and message of the light status
+    fn light(diagnostics: &DiagnosticsPublisher) -> (u8, String) {
+        let array = diagnostics.collect();
+        assert_eq!(array.status.len(), 2);
+        let status = &array.status[1];
+        assert_eq!((status.name.as_str(), status.hardware_id.as_str()), ("coffee_maker_driver: light", "@LGT"));
+        (status.level, status.message.clone())
+    }
+
+    fn value<'a>(status: &'a DiagnosticStatus, key: &str) -> Option<&'a str> {
+        status.values.iter().find(|value| value.key == key).map(|value| value.value.as_str())
+    }
+
+    #[test]
+    fn link_status_follows_the_transport() {
+        let (diagnostics, _states, link) = diagnostics(None);
+        let status = diagnostics.link_status();
+        assert_eq!((status.level, status.message.as_str()), (DiagnosticStatus::ERROR, "disconnected"));
+        assert_eq!((status.name.as_str(), status.hardware_id.as_str()), ("coffee_maker_driver: mqtt link", "localhost:1883"));
+
+        link.0.store(true, Ordering::SeqCst);
+        let status = diagnostics.link_status();
+        assert_eq!((status.level, status.message.as_str()), (DiagnosticStatus::OK, "connected"));
+        assert_eq!(value(&status, "connected"), Some("true"));
+    }
+
+    #[test]
+    fn module_level_follows_its_frames() {
+        let (diagnostics, states, _link) = diagnostics(None);
+        assert_eq!(light(&diagnostics), (DiagnosticStatus::STALE, "no frame received".to_string()));
+
+        frame_rejected(&states);
+        assert_eq!(light(&diagnostics), (DiagnosticStatus::ERROR, "no frame decoded, 1 rejected".to_string()));
+
+        frame_received(&states);
+        let status = &diagnostics.collect().status[1];
+        assert_eq!((status.level, status.message.as_str()), (DiagnosticStatus::OK, "online"));
+        assert_eq!(value(status, "frames_decoded"), Some("1"));
+        assert_eq!(value(status, "frames_rejected"), Some("1"));
+        assert_eq!(value(status, "errors.checksum_mismatch"), Some("1"));
+        assert_eq!(value(status, "checksum_error_rate"), Some("0.5000"));
+        assert_eq!(value(status, "state"), Some("1"));
+        assert_eq!(value(status, "connected"), Some("true"));
+
+        // a rejected frame is a warning of the next report only
+        frame_rejected(&states);
+        frame_rejected(&states);
+        assert_eq!(light(&diagnostics), (DiagnosticStatus::WARN, "2 frame(s) rejected since the last report".to_string()));
+        assert_eq!(light(&diagnostics).0, DiagnosticStatus::OK);
+    }
+
+    #[test]
+    fn module_without_watchdog_is_offline_after_stale_after_ms() {
+        let (diagnostics, states, _link) = diagnostics(None);
+        frame_received(&states);
+        assert_eq!(light(&diagnostics).0, DiagnosticStatus::OK);
+
+        thread::sleep(Duration::from_millis(STALE_AFTER_MS));
+        let (level, message) = light(&diagnostics);
+        assert_eq!(level, DiagnosticStatus::ERROR);
+        assert!(message.starts_with("offline, no frame for"), "{}", message);
+        assert_eq!(value(&diagnostics.collect().status[1], "connected"), Some("false"));
+    }
+
+    #[test]
+    fn watchdog_timeout_replaces_stale_after_ms() {
+        let (diagnostics, states, _link) = diagnostics(Some(10_000));
+        frame_received(&states);
+        thread::sleep(Duration::from_millis(STALE_AFTER_MS));
+        assert_eq!(light(&diagnostics), (DiagnosticStatus::OK, "online".to_string()));
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde::Deserialize;
+use std::{collections::HashSet, fs, path::Path, time::Duration};
+
+use crate::command_catalog::{CommandCatalog, CommandRef};
+use crate::module_layout::{Comparison, ModuleLayouts};
+
+pub mod interlock_guard;
+
+pub use interlock_guard::InterlockGuard;
+
+///////////////////////////////////////////////////////////
+//                   Struct Defination                 ////
+///////////////////////////////////////////////////////////
+
+/// content of an interlock file, one `[[interlock]]` table per rule
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Interlocks {
+    #[serde(default)]
+    pub interlock: Vec<InterlockRule>,
+}
+
+/// `command` of `module` is only sent while every `require` condition holds
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterlockRule {
+    pub name: String,
+    pub module: String,
+    pub command: CommandRef,
+    pub require: Vec<InterlockCondition>,
+}
+
+/// latest decoded `field` of `module` compared to `value`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterlockCondition {
+    pub module: String,
+    pub field: String,
+    pub compare: Comparison,
+    pub value: i64,
+    /// allowed difference for `near`
+    #[serde(default)]
+    pub tolerance: i64,
+    /// an older state of `module` counts as unknown, as does the state of a module its
+    /// watchdog flags offline
+    #[serde(default = "InterlockCondition::default_max_age_ms")]
+    pub max_age_ms: u64,
+}
+
+///////////////////////////////////////////////////////////
+//                   Struct Implementation             ////
+///////////////////////////////////////////////////////////
+
+impl Interlocks {
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               construction                                             ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn from_toml(content: &str, module_layouts: &ModuleLayouts) -> Result<Self, Error> {
+        let interlocks: Interlocks = toml::from_str(content)?;
+        interlocks.validate(module_layouts)?;
+        Ok(interlocks)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P, module_layouts: &ModuleLayouts) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("cannot read interlock file '{}'", path.display()))?;
+        Self::from_toml(&content, module_layouts)
+            .with_context(|| format!("invalid interlock file '{}'", path.display()))
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               class functions                                          ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn validate(&self, module_layouts: &ModuleLayouts) -> Result<(), Error> {
+        let mut names = HashSet::new();
+        for rule in &self.interlock {
+            if !names.insert(rule.name.as_str()) {
+                bail!("interlock '{}' is defined more than once", rule.name);
+            }
+            rule.code(module_layouts)
+                .with_context(|| format!("[{}] invalid interlock", rule.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl InterlockRule {
+    /// check the rule against the layouts and resolve the code of its command
+    pub fn code(&self, module_layouts: &ModuleLayouts) -> Result<u8, Error> {
+        let layout = module_layouts.get(&self.module)?;
+        let code = CommandCatalog::new(layout)
+            .code(&self.command)
+            .ok_or_else(|| anyhow!("module '{}' has no command {}", self.module, self.command))?;
+        if code as u64 > layout.max_command() {
+            bail!("command {} does not fit the command field, the maximum is {}", code, layout.max_command());
+        }
+
+        if self.require.is_empty() {
+            bail!("interlock has no 'require' condition");
+        }
+        for condition in &self.require {
+            let fields = module_layouts.get(&condition.module)?.fields();
+            if !fields.contains(condition.field.as_str()) {
+                bail!(
+                    "field '{}' of module '{}' is neither 'state' nor a bit field target",
+                    condition.field, condition.module
+                );
+            }
+            if condition.tolerance < 0 {
+                bail!("tolerance {} must not be negative", condition.tolerance);
+            }
+            if condition.max_age_ms == 0 {
+                bail!("max_age_ms of {} {} must be positive", condition.module, condition.field);
+            }
+        }
+        Ok(code)
+    }
+}
+
+impl InterlockCondition {
+    fn default_max_age_ms() -> u64 {
+        2000
+    }
+
+    pub fn max_age(&self) -> Duration {
+        Duration::from_millis(self.max_age_ms)
+    }
+
+    pub fn is_met(&self, current: i64) -> bool {
+        self.compare.matches(current, self.value, self.tolerance)
+    }
+
+    /// short description used in rejection messages
+    pub fn describe(&self) -> String {
+        match self.compare {
+            Comparison::Near => format!(
+                "{} {} near {} (+/- {})", self.module, self.field, self.value, self.tolerance
+            ),
+            compare => format!("{} {} {} {}", self.module, self.field, compare.name(), self.value),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use super::{InterlockCondition, InterlockRule, Interlocks};
+use crate::command_tracker::CommandError;
+use crate::module_layout::ModuleLayouts;
+use crate::module_state::{ModuleSnapshot, ModuleStates};
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::Duration};
+use anyhow::{Error, Result};
+use log::{error, warn};
+use rclrs::{Node, Publisher};
+use std_msgs::msg::String as StringMsg;
+
+/// interlock rules checked against the latest decoded state of the modules
+/// before a `/set` frame is sent
+pub struct InterlockGuard {
+    /// rules with the resolved code of their command
+    rules: Vec<(InterlockRule, u8)>,
+    /// watchdog timeout of every module that has one, an older state is offline
+    offline_after: HashMap<String, Duration>,
+    states: Arc<ModuleStates>,
+    /// `interlock/rejected`, why a command was not sent
+    rejected_publisher: Mutex<Option<Arc<Publisher<StringMsg>>>>,
+}
+
+impl InterlockGuard {
+    pub fn new(interlocks: &Interlocks, module_layouts: &ModuleLayouts, states: Arc<ModuleStates>) -> Result<Self, Error> {
+        let rules = interlocks.interlock
+            .iter()
+            .map(|rule| Ok((rule.clone(), rule.code(module_layouts)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let offline_after = module_layouts.module
+            .iter()
+            .filter_map(|layout| layout.watchdog.as_ref().map(|watchdog| (layout.name.clone(), watchdog.timeout())))
+            .collect();
+        Ok(Self {
+            rules,
+            offline_after,
+            states,
+            rejected_publisher: Mutex::new(None),
+        })
+    }
+
+    /// guard without rules, every command passes
+    pub fn unrestricted(states: Arc<ModuleStates>) -> Self {
+        Self {
+            rules: Vec::new(),
+            offline_after: HashMap::new(),
+            states,
+            rejected_publisher: Mutex::new(None),
+        }
+    }
+
+    pub fn start(&self, node: &Arc<Node>) -> Result<(), Error> {
+        let publisher = node.create_publisher::<StringMsg>("interlock/rejected", rclrs::QOS_PROFILE_DEFAULT)?;
+        match self.rejected_publisher.lock() {
+            Ok(mut publisher_guard) => *publisher_guard = Some(publisher),
+            Err(_) => error!("Failed to acquire lock for rejected_publisher"),
+        }
+        Ok(())
+    }
+
+    /// check `command` of `module` against every rule, a module whose state is not
+    /// known yet, older than `max_age_ms` or offline fails the conditions on it
+    pub fn check(&self, module: &str, command: u8) -> Result<(), CommandError> {
+        let rules = self.rules
+            .iter()
+            .filter(|(rule, code)| rule.module == module && *code == command);
+        for (rule, _) in rules {
+            for condition in &rule.require {
+                let snapshot = self.states.get(&condition.module);
+                let age = snapshot.as_ref().map(ModuleSnapshot::age);
+                let current = snapshot
+                    .filter(|snapshot| snapshot.age() < self.max_age(condition))
+                    .and_then(|snapshot| snapshot.values.get(&condition.field));
+                let reason = match (current, age) {
+                    (Some(current), _) if condition.is_met(current) => continue,
+                    (Some(current), _) => format!("{} is {}, needs {}", condition.field, current, condition.describe()),
+                    (None, Some(age)) => format!(
+                        "state of {} is {} ms old, needs {}", condition.module, age.as_millis(), condition.describe()
+                    ),
+                    (None, None) => format!("state of {} is unknown, needs {}", condition.module, condition.describe()),
+                };
+                let error = CommandError::Interlocked { rule: rule.name.clone(), reason };
+                self.publish_rejected(&format!("[{}] command {} rejected: {}", module, command, error));
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// age up to which the state of the module of `condition` is trusted
+    fn max_age(&self, condition: &InterlockCondition) -> Duration {
+        match self.offline_after.get(&condition.module) {
+            Some(timeout) => condition.max_age().min(*timeout),
+            None => condition.max_age(),
+        }
+    }
+
+    fn publish_rejected(&self, message: &str) {
+        warn!("{}", message);
+        match self.rejected_publisher.lock() {
+            Ok(publisher_guard) => {
+                if let Some(publisher) = publisher_guard.as_ref() {
+                    if let Err(e) = publisher.publish(StringMsg { data: message.to_string() }) {
+                        error!("Failed to publish rejection: {:?}", e);
+                    }
+                }
+            }
+            Err(_) => error!("Failed to acquire lock for rejected_publisher"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module_msg_converter::DecodedValues;
+    use std::thread;
+
+    const INTERLOCKS: &str = r#"
+        [[interlock]]
+        name = "fill_water_needs_water"
+        module = "coffee_feeder"
+        command = "fill_water"
+
+        [[interlock.require]]
+        module = "Tank"
+        field = "water_quantity"
+        compare = "at_least"
+        value = 1
+        max_age_ms = 50
+    "#;
+
+    fn guard(layouts: &str) -> (InterlockGuard, Arc<ModuleStates>) {
+        let module_layouts = ModuleLayouts::from_toml(layouts).unwrap();
+        let interlocks = Interlocks::from_toml(INTERLOCKS, &module_layouts).unwrap();
+        let states = Arc::new(ModuleStates::new());
+        (InterlockGuard::new(&interlocks, &module_layouts, states.clone()).unwrap(), states)
+    }
+
+    fn tank(water_quantity: i64) -> DecodedValues {
+        DecodedValues { state: 1, fields: vec![("water_quantity".to_string(), water_quantity)] }
+    }
+
+    #[test]
+    fn blocks_until_the_condition_holds() {
+        let (guard, states) = guard(include_str!("../../config/modules.toml"));
+        assert!(matches!(guard.check("coffee_feeder", 1), Err(CommandError::Interlocked { .. })));
+
+        states.update("Tank", tank(0));
+        assert!(matches!(guard.check("coffee_feeder", 1), Err(CommandError::Interlocked { .. })));
+        states.update("Tank", tank(5));
+        assert_eq!(guard.check("coffee_feeder", 1), Ok(()));
+        // other commands are not guarded
+        assert_eq!(guard.check("coffee_feeder", 0), Ok(()));
+    }
+
+    #[test]
+    fn an_old_state_is_unknown() {
+        let (guard, states) = guard(include_str!("../../config/modules.toml"));
+        states.update("Tank", tank(5));
+        assert_eq!(guard.check("coffee_feeder", 1), Ok(()));
+
+        thread::sleep(Duration::from_millis(60));
+        match guard.check("coffee_feeder", 1) {
+            Err(CommandError::Interlocked { reason, .. }) => assert!(reason.contains("ms old"), "{}", reason),
+            other => panic!("expected an interlock, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn an_offline_module_is_unknown() {
+        // the watchdog of the tank flags it offline well before `max_age_ms`
+        let layouts = include_str!("../../config/modules.toml").replace(
+            "name = \"Tank\"\n",
+            "name = \"Tank\"\n\n[module.watchdog]\ntimeout_ms = 10\n",
+        );
+        let (guard, states) = guard(&layouts);
+        states.update("Tank", tank(5));
+        thread::sleep(Duration::from_millis(20));
+        assert!(matches!(guard.check("coffee_feeder", 1), Err(CommandError::Interlocked { .. })));
+    }
+}
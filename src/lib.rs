@@ -1,2 +1,16 @@
+pub mod command_catalog;
+pub mod command_tracker;
+pub mod config;
+pub mod converter_registry;
+pub mod diagnostics;
+pub mod interlock;
+pub mod logging;
+pub mod module_layout;
+pub mod module_state;
 pub mod module_struct;
-pub mod module_msg_converter;
\ No newline at end of file
+pub mod module_msg_converter;
+pub mod module_operation;
+pub mod module_watchdog;
+pub mod recipe;
+pub mod simulator;
+pub mod transport;
\ No newline at end of file
@@ -0,0 +1,235 @@
+use crate::config::LogConfig;
+use std::{collections::HashMap, str::FromStr, sync::{Arc, Mutex, OnceLock, RwLock}, time::SystemTime};
+use anyhow::{anyhow, Error, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use rclrs::{Node, Publisher, Subscription};
+use rcl_interfaces::msg::Log as LogMsg;
+use std_msgs::msg::String as StringMsg;
+
+use crate::module_state::time_msg;
+
+static LOGGER: OnceLock<RosLogger> = OnceLock::new();
+
+/// `log` backend writing to stderr and to `/rosout`, the topic behind the ROS logger,
+/// with a level per target. converters log with their namespace as target, everything
+/// else with its module path
+pub struct RosLogger {
+    default_level: RwLock<LevelFilter>,
+    /// level of a target and of the targets below it (`<target>::...`)
+    target_levels: RwLock<HashMap<String, LevelFilter>>,
+    node_name: Mutex<String>,
+    rosout_publisher: Mutex<Option<Arc<Publisher<LogMsg>>>>,
+    set_level_subscription: Mutex<Option<Arc<Subscription<StringMsg>>>>,
+}
+
+/// install the logger, log records are written to stderr until `start` connects it to ROS
+pub fn init(config: &LogConfig) -> Result<&'static RosLogger, Error> {
+    if LOGGER.set(RosLogger::new(config)?).is_err() {
+        return Err(anyhow!("logger is already initialized"));
+    }
+    let logger = LOGGER.get().ok_or_else(|| anyhow!("logger is not initialized"))?;
+    log::set_logger(logger).map_err(|e| anyhow!("cannot install logger: {}", e))?;
+    logger.update_max_level();
+    Ok(logger)
+}
+
+impl RosLogger {
+    fn new(config: &LogConfig) -> Result<Self, Error> {
+        Ok(Self {
+            default_level: RwLock::new(parse_level(&config.level)?),
+            target_levels: RwLock::new(
+                config.targets
+                    .iter()
+                    .map(|(target, level)| Ok((target.clone(), parse_level(level)?)))
+                    .collect::<Result<_, Error>>()?,
+            ),
+            node_name: Mutex::new(String::new()),
+            rosout_publisher: Mutex::new(None),
+            set_level_subscription: Mutex::new(None),
+        })
+    }
+
+    /// publish records on `/rosout` and accept level changes on `log/set_level`,
+    /// either `<level>` for the default level or `<target>=<level>`
+    pub fn start(&'static self, node: &Arc<Node>, node_name: &str) -> Result<(), Error> {
+        let publisher = node.create_publisher::<LogMsg>("/rosout", rclrs::QOS_PROFILE_DEFAULT)?;
+        let subscription = node.create_subscription::<StringMsg, _>(
+            "log/set_level",
+            rclrs::QOS_PROFILE_DEFAULT,
+            move |msg: StringMsg| {
+                if let Err(e) = self.set_level(&msg.data) {
+                    log::warn!("cannot set log level '{}': {:#}", msg.data, e);
+                }
+            },
+        )?;
+
+        if let Ok(mut name) = self.node_name.lock() {
+            *name = node_name.to_string();
+        }
+        if let Ok(mut publisher_guard) = self.rosout_publisher.lock() {
+            *publisher_guard = Some(publisher);
+        }
+        if let Ok(mut subscription_guard) = self.set_level_subscription.lock() {
+            *subscription_guard = Some(subscription);
+        }
+        Ok(())
+    }
+
+    /// apply `<level>` or `<target>=<level>`
+    pub fn set_level(&self, spec: &str) -> Result<(), Error> {
+        match spec.split_once('=') {
+            Some((target, level)) => {
+                let level = parse_level(level)?;
+                if let Ok(mut levels) = self.target_levels.write() {
+                    levels.insert(target.trim().to_string(), level);
+                }
+            }
+            None => {
+                let level = parse_level(spec)?;
+                if let Ok(mut default_level) = self.default_level.write() {
+                    *default_level = level;
+                }
+            }
+        }
+        self.update_max_level();
+        log::info!("log level set to '{}'", spec.trim());
+        Ok(())
+    }
+
+    /// level of the longest configured target matching `target`
+    fn level(&self, target: &str) -> LevelFilter {
+        let default_level = self.default_level.read().map(|level| *level).unwrap_or(LevelFilter::Info);
+        let levels = match self.target_levels.read() {
+            Ok(levels) => levels,
+            Err(_) => return default_level,
+        };
+        levels
+            .iter()
+            .filter(|(name, _)| {
+                target == name.as_str()
+                    || target.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(default_level)
+    }
+
+    /// let `log` skip records no target can accept before formatting them
+    fn update_max_level(&self) {
+        let default_level = self.default_level.read().map(|level| *level).unwrap_or(LevelFilter::Info);
+        let max_level = self.target_levels
+            .read()
+            .ok()
+            .and_then(|levels| levels.values().max().copied())
+            .map_or(default_level, |level| level.max(default_level));
+        log::set_max_level(max_level);
+    }
+
+    fn publish(&self, record: &Record) {
+        let publisher = match self.rosout_publisher.lock() {
+            Ok(publisher_guard) => match publisher_guard.as_ref() {
+                Some(publisher) => publisher.clone(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        let node_name = self.node_name.lock().map(|name| name.clone()).unwrap_or_default();
+        let msg = LogMsg {
+            stamp: time_msg(SystemTime::now()),
+            level: rosout_level(record.level()),
+            name: format!("{}.{}", node_name, record.target().replace("::", ".")),
+            msg: record.args().to_string(),
+            file: record.file().unwrap_or_default().to_string(),
+            function: record.module_path().unwrap_or_default().to_string(),
+            line: record.line().unwrap_or_default(),
+        };
+        // a failed publication must not log again
+        let _ = publisher.publish(msg);
+    }
+}
+
+impl Log for RosLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        eprintln!("[{}] [{}] {}", record.level(), record.target(), record.args());
+        self.publish(record);
+    }
+
+    fn flush(&self) {}
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, Error> {
+    LevelFilter::from_str(level.trim())
+        .map_err(|_| anyhow!("unknown log level '{}', use off, error, warn, info, debug or trace", level.trim()))
+}
+
+/// severity of `rcl_interfaces/msg/Log`, ROS has no trace level
+fn rosout_level(level: Level) -> u8 {
+    match level {
+        Level::Error => LogMsg::ERROR,
+        Level::Warn => LogMsg::WARN,
+        Level::Info => LogMsg::INFO,
+        Level::Debug | Level::Trace => LogMsg::DEBUG,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn logger(level: &str, targets: &[(&str, &str)]) -> RosLogger {
+        let targets: BTreeMap<String, String> = targets
+            .iter()
+            .map(|(target, level)| (target.to_string(), level.to_string()))
+            .collect();
+        RosLogger::new(&LogConfig { level: level.to_string(), targets }).unwrap()
+    }
+
+    #[test]
+    fn targets_without_level_use_the_default() {
+        let logger = logger("warn", &[("coffee_feeder", "trace")]);
+        assert_eq!(logger.level("light"), LevelFilter::Warn);
+        assert_eq!(logger.level("coffee_maker_driver::simulator"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn exact_target_and_its_children_take_its_level() {
+        let logger = logger("info", &[("coffee_feeder", "trace"), ("coffee_maker_driver::transport", "debug")]);
+        assert_eq!(logger.level("coffee_feeder"), LevelFilter::Trace);
+        assert_eq!(logger.level("coffee_maker_driver::transport"), LevelFilter::Debug);
+        assert_eq!(logger.level("coffee_maker_driver::transport::mqtt_transport"), LevelFilter::Debug);
+        // a prefix only matches whole path segments
+        assert_eq!(logger.level("coffee_feeder_2"), LevelFilter::Info);
+        assert_eq!(logger.level("coffee_maker_driver::transport_test"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn longest_matching_prefix_wins() {
+        let logger = logger("info", &[
+            ("coffee_maker_driver", "error"),
+            ("coffee_maker_driver::transport", "debug"),
+            ("coffee_maker_driver::transport::serial_transport", "trace"),
+        ]);
+        assert_eq!(logger.level("coffee_maker_driver::diagnostics"), LevelFilter::Error);
+        assert_eq!(logger.level("coffee_maker_driver::transport::mqtt_transport"), LevelFilter::Debug);
+        assert_eq!(logger.level("coffee_maker_driver::transport::serial_transport"), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn set_level_changes_the_default_or_one_target() {
+        let logger = logger("info", &[]);
+        logger.set_level("light = debug").unwrap();
+        logger.set_level("error").unwrap();
+        assert_eq!(logger.level("light"), LevelFilter::Debug);
+        assert_eq!(logger.level("Tank"), LevelFilter::Error);
+        assert!(logger.set_level("light=loud").is_err());
+        assert_eq!(logger.level("light"), LevelFilter::Debug);
+    }
+}
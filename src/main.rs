@@ -1,130 +1,137 @@
-#![allow(dead_code)]
-#![allow(unused)]
-
-use coffee_maker_driver::module_msg_converter::{
-    capsule_feeder_converter::CapsuleFeederConverter, coffee_feeder_converter::CoffeeFeederConverter, cup_holder_converter::{self, CupHolderConverter}, light_converter::{self, LightConverter}, pdu_converter::{self, PDUConverter}, tank_converter::{self, TankConverter}, Converter
-};
-
-use obd_coffee_maker_interface::msg::{
-    CoffeeFeederInput, CoffeeFeederOutput,
-    CapsuleFeederInput, CapsuleFeederOutput,
-    CupHolderInput, CupHolderOutput,
-    TankInput, TankOutput,
-    PDUInput, PDUOutput,
-    LightInput, LightOutput};
-
-use std::{env, sync::{Arc, Mutex}};
-use anyhow::{Error, Result};
-use std::collections::HashMap;
-use rclrs::{Node, Context, Subscription};
-use std::thread;
-use std::time::Duration;
-use tokio::task;
-use rumqttc::{MqttOptions, Client, QoS, Event, Packet};
-
-enum ConvertersEnum {
-    CoffeeFeeder(CoffeeFeederConverter),
-    CapsuleFeeder(CapsuleFeederConverter),
-    CupHolder(CupHolderConverter),
-    Tank(TankConverter),
-    Pdu(PDUConverter),
-    Light(LightConverter),
-
+use coffee_maker_driver::config::{self, DriverConfig, TransportKind};
+use coffee_maker_driver::converter_registry::ConverterRegistry;
+use coffee_maker_driver::diagnostics::{DiagnosticsPublisher, LinkInfo};
+use coffee_maker_driver::interlock::{InterlockGuard, Interlocks};
+use coffee_maker_driver::logging;
+use coffee_maker_driver::module_layout::ModuleLayouts;
+use coffee_maker_driver::module_state::{ModuleStateServer, ModuleStates};
+use coffee_maker_driver::module_watchdog::ModuleWatchdog;
+use coffee_maker_driver::recipe::{RecipeExecutor, RecipeServer, Recipes};
+use coffee_maker_driver::simulator::Simulator;
+use coffee_maker_driver::transport::{CaptureTransport, FrameTransport, MqttTransport, SerialTransport, SimulatorTransport};
+
+use std_msgs::msg::Bool as BoolMsg;
+
+use std::{env, path::PathBuf, sync::Arc};
+use anyhow::{anyhow, Error, Result};
+use rclrs::{Node, Context};
+
+const NODE_NAME: &str = "coffee_machine_driver";
+
+fn load_config(node: &Node, args: Vec<String>) -> Result<DriverConfig, Error> {
+    let config_path = match config::config_path_from_args(args)? {
+        Some(path) => path,
+        None => node
+            .declare_parameter::<Arc<str>>(config::CONFIG_PARAM)
+            .optional()?
+            .get()
+            .map(|path| PathBuf::from(path.as_ref()))
+            .ok_or_else(|| anyhow!(
+                "no config file given, use '{} <path>' or the '{}' ROS parameter",
+                config::CONFIG_ARG, config::CONFIG_PARAM
+            ))?,
+    };
+    DriverConfig::from_file(&config_path)
 }
 
-impl Clone for ConvertersEnum {
-    fn clone(&self) -> ConvertersEnum {
-        match self {
-            ConvertersEnum::CoffeeFeeder(coffee_feeder) => ConvertersEnum::CoffeeFeeder(coffee_feeder.clone()),
-            ConvertersEnum::CapsuleFeeder(capsule_feeder) => ConvertersEnum::CapsuleFeeder(capsule_feeder.clone()),
-            ConvertersEnum::CupHolder(cup_holder) => ConvertersEnum::CupHolder(cup_holder.clone()),
-            ConvertersEnum::Tank(tank) => ConvertersEnum::Tank(tank.clone()),
-            ConvertersEnum::Pdu(pdu) => ConvertersEnum::Pdu(pdu.clone()),
-            ConvertersEnum::Light(light) => ConvertersEnum::Light(light.clone())
+fn main() -> Result<(), Box<dyn std::error::Error>>{
+    let args: Vec<String> = env::args().collect();
+    let ctx = Context::new(args.clone())?;
+    let node = rclrs::create_node(&ctx, NODE_NAME)?;
+
+    // the logger needs the config, a config error can only go to stderr
+    let driver_config = match load_config(&node, args) {
+        Ok(driver_config) => driver_config,
+        Err(e) => {
+            eprintln!("[{}] failed to load config: {:#}", NODE_NAME, e);
+            return Err(e.into());
         }
-    }
-}
-
-impl ConvertersEnum {
-    fn handle_mqtt_message(&self, topic: &str, payload: &str) {
-        match self {
-            ConvertersEnum::CoffeeFeeder(c) => c.handle_mqtt_message(topic, payload),
-            ConvertersEnum::CapsuleFeeder(c) => c.handle_mqtt_message(topic, payload),
-            ConvertersEnum::CupHolder(c) => c.handle_mqtt_message(topic, payload),
-            ConvertersEnum::Tank(c) => c.handle_mqtt_message(topic, payload),
-            ConvertersEnum::Pdu(c) => c.handle_mqtt_message(topic, payload),
-            ConvertersEnum::Light(c) => c.handle_mqtt_message(topic, payload),
-        }
-    }
-}
+    };
+    logging::init(&driver_config.log)?.start(&node, NODE_NAME)?;
 
+    let module_layouts = ModuleLayouts::from_file(&driver_config.module_layout_file)?;
 
-fn main() -> Result<(), Box<dyn std::error::Error>>{
-    let ctx = Context::new(env::args())?;
-    let node = rclrs::create_node(&ctx, "coffee_machine_driver")?;
+    let (transport, link): (Arc<dyn FrameTransport>, LinkInfo) = match driver_config.transport {
+        TransportKind::Mqtt => {
+            let mqtt = driver_config.mqtt()?;
+            let link = LinkInfo { name: "mqtt".to_string(), hardware_id: format!("{}:{}", mqtt.host, mqtt.port) };
+            (Arc::new(MqttTransport::new(mqtt)), link)
+        }
+        TransportKind::Serial => {
+            let serial = driver_config.serial()?;
+            let link = LinkInfo { name: "serial".to_string(), hardware_id: serial.port.clone() };
+            (Arc::new(SerialTransport::open(serial, &module_layouts)?), link)
+        }
+        TransportKind::Simulator => {
+            let link = LinkInfo { name: "simulator".to_string(), hardware_id: "in-process".to_string() };
+            (Arc::new(SimulatorTransport::new(Simulator::new(&module_layouts, &driver_config.simulator), &driver_config.simulator)), link)
+        }
+    };
+    let transport: Arc<dyn FrameTransport> = match &driver_config.capture_file {
+        Some(capture_file) => {
+            log::info!("capturing frames to '{}'", capture_file.display());
+            Arc::new(CaptureTransport::create(transport, capture_file)?)
+        }
+        None => transport,
+    };
 
-    let mut mqtt_options = MqttOptions::new("test-rust", "192.168.1.101", 1883);
-    mqtt_options.set_credentials("khadas-master", "droid");
+    let states = Arc::new(ModuleStates::new());
+    // keep the state server alive while spinning
+    let _state_server = ModuleStateServer::start(&node, states.clone())?;
 
-    let (mqtt_client, mut mqtt_connection) = Client::new(mqtt_options, 10);
+    let interlock = Arc::new(match &driver_config.interlock_file {
+        Some(interlock_file) => {
+            let interlocks = Interlocks::from_file(interlock_file, &module_layouts)?;
+            InterlockGuard::new(&interlocks, &module_layouts, states.clone())?
+        }
+        None => InterlockGuard::unrestricted(states.clone()),
+    });
+    interlock.start(&node)?;
 
-    let mqtt_client = Arc::new(Mutex::new(mqtt_client));
+    let converters = ConverterRegistry::with_modules(
+        transport.clone(), node.clone(), &module_layouts, &driver_config.command, states.clone(), interlock.clone()
+    )?;
+    converters.start_all()?;
 
-    let coffee_feeder = CoffeeFeederConverter::new(mqtt_client.clone(), node.clone());
-    let capsule_feeder  = CapsuleFeederConverter::new(mqtt_client.clone(), node.clone());
-    let cup_holder = CupHolderConverter::new(mqtt_client.clone(), node.clone());
-    let light = LightConverter::new(mqtt_client.clone(), node.clone());
-    let pdu = PDUConverter::new(mqtt_client.clone(), node.clone());
-    let tank = TankConverter::new(mqtt_client.clone(), node.clone());
+    let connection_publisher = node.create_publisher::<BoolMsg>("connection_state", rclrs::QOS_PROFILE_DEFAULT)?;
+    transport.watch_connection(Box::new(move |connected| {
+        if let Err(e) = connection_publisher.publish(BoolMsg { data: connected }) {
+            log::error!("Failed to publish connection state: {:?}", e);
+        }
+    }))?;
 
-    let mut converters: HashMap<String, ConvertersEnum> = HashMap::new();
+    let converters = Arc::new(converters);
 
-    coffee_feeder.start()?;
-    capsule_feeder.start()?;
-    cup_holder.start()?;
-    light.start()?;
-    pdu.start()?;
-    tank.start()?;
+    // keep the diagnostics publisher alive while spinning
+    let _diagnostics = DiagnosticsPublisher::start(
+        &node, &driver_config.diagnostics, &module_layouts, states.clone(), transport.clone(), link
+    )?;
 
-    converters.insert(coffee_feeder.name.clone(), ConvertersEnum::CoffeeFeeder(coffee_feeder));
-    converters.insert(capsule_feeder.name.clone(), ConvertersEnum::CapsuleFeeder(capsule_feeder));
-    converters.insert(cup_holder.name.clone(), ConvertersEnum::CupHolder(cup_holder));
-    converters.insert(light.name.clone(), ConvertersEnum::Light(light));
-    converters.insert(pdu.name.clone(), ConvertersEnum::Pdu(pdu));
-    converters.insert(tank.name.clone(), ConvertersEnum::Tank(tank));
+    // keep the watchdogs alive while spinning
+    let mut watchdogs = Vec::new();
+    for layout in &module_layouts.module {
+        watchdogs.extend(ModuleWatchdog::start(&node, layout, states.clone(), transport.clone())?);
+    }
 
- 
+    // keep the recipe server alive while spinning
+    let _recipe_server = match &driver_config.recipe_file {
+        Some(recipe_file) => {
+            let recipes = Recipes::from_file(recipe_file, &module_layouts)?;
+            let executor = Arc::new(RecipeExecutor::new(converters.clone(), &recipes, &module_layouts)?);
+            Some(RecipeServer::start(&node, executor)?)
+        }
+        None => None,
+    };
     let converters_clone = converters.clone();
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            loop {
-                if let Ok(Event::Incoming(Packet::Publish(publish))) = mqtt_connection.eventloop.poll().await {
-                    let topic = publish.topic.clone();
-
-                    // Extract namespace from topic
-                    let parts: Vec<&str> = topic.split('/').collect();
-                    if parts.len() >= 2 && parts[1] == "get" {
-                        let namespace = parts[0].to_string();
-                        
-                        // Publish to ROS using the stored publisher
-                        if let Some(converter_enum) = converters_clone.get(&namespace) {
-                            if let Ok(payload) = String::from_utf8(publish.payload.to_vec()){
-                                converter_enum.handle_mqtt_message(&topic, &payload);
-                            } else {
-                                eprint!("cannot convert bytes to String");
-                            }
-                        } else {
-                            eprint!("incorrect format 'name/get' mqtt topic: {}", topic);
-                        }
-                    }
-                }
-            }
-        });
-    });
-
-    rclrs::spin(node).unwrap();
+    transport.start_receiving(Box::new(move |namespace, frame| {
+        // Publish to ROS using the stored publisher, decode errors are reported by the converter
+        let _ = converters_clone.dispatch(namespace, frame);
+    }))?;
+
+    if let Err(e) = rclrs::spin(node) {
+        log::error!("executor stopped: {}", e);
+        return Err(e.into());
+    }
 
     Ok(())
-    
 }
\ No newline at end of file
@@ -0,0 +1,803 @@
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde::Deserialize;
+use std::{collections::HashSet, fs, path::Path, time::Duration};
+
+use crate::command_catalog::{CommandCatalog, CommandRef};
+use crate::module_struct::{BitField, ModuleDataField, ModuleHead, ModuleInputFormat, ModuleOutputFormat, ModuleTail};
+
+/// topics every module owns below its namespace
+const RESERVED_TOPICS: [&str; 6] = ["input", "output", "command", "command_result", "named_command", "online"];
+
+///////////////////////////////////////////////////////////
+//                   Struct Defination                 ////
+///////////////////////////////////////////////////////////
+
+/// content of a module layout file, one `[[module]]` table per module
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModuleLayouts {
+    pub module: Vec<ModuleLayout>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModuleLayout {
+    pub name: String,
+    pub input: InputLayout,
+    pub output: OutputLayout,
+    /// command catalog, one `[[module.command]]` table each. when given, only listed
+    /// commands with a value in their range are sent
+    #[serde(default)]
+    pub command: Vec<CommandLayout>,
+    /// `state` values the module reports when it refused or failed a command
+    #[serde(default)]
+    pub error_states: Vec<u8>,
+    /// long running commands, one `[[module.operation]]` table each
+    #[serde(default)]
+    pub operation: Vec<OperationLayout>,
+    /// flag the module offline when its `/get` frames stop, disabled when omitted
+    #[serde(default)]
+    pub watchdog: Option<WatchdogLayout>,
+}
+
+/// frame layout of a `/set` message
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputLayout {
+    pub header: FieldLayout,
+    pub package: FieldLayout,
+    pub setting: FieldLayout,
+    pub length: FieldLayout,
+    pub command: FieldLayout,
+    pub value: FieldLayout,
+    pub lrc: FieldLayout,
+    pub end: FieldLayout,
+}
+
+/// frame layout of a `/get` message
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputLayout {
+    pub header: FieldLayout,
+    pub package: FieldLayout,
+    pub setting: FieldLayout,
+    pub length: FieldLayout,
+    pub state: FieldLayout,
+    pub payload: Vec<PayloadFieldLayout>,
+    pub lrc: FieldLayout,
+    pub end: FieldLayout,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldLayout {
+    pub index: usize,
+    pub size: usize,
+    /// fixed content of the field, zero filled when omitted
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PayloadFieldLayout {
+    pub name: String,
+    pub index: usize,
+    pub size: usize,
+    /// first character carries the sign ('-' or '0') of every bit field
+    #[serde(default)]
+    pub signed: bool,
+    #[serde(default)]
+    pub bits: Vec<BitFieldLayout>,
+}
+
+/// bits of the decimal value of a payload field, counted from the least significant bit
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BitFieldLayout {
+    pub name: String,
+    pub offset: u32,
+    pub width: u32,
+    /// two's complement value of `width` bits
+    #[serde(default)]
+    pub signed: bool,
+    /// output message field written with the value, defaults to `name`;
+    /// list elements are addressed as `field[index]`
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+/// named command of a module
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandLayout {
+    pub name: String,
+    pub code: u8,
+    /// accepted values, inclusive. the whole value field when omitted
+    #[serde(default)]
+    pub min: u16,
+    #[serde(default = "CommandLayout::default_max")]
+    pub max: u16,
+    /// unit of the value, documentation only
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// `state` the module reports once it executed the command, frames with an other
+    /// state do not acknowledge it. any state but an error state when omitted
+    #[serde(default)]
+    pub state: Option<u8>,
+}
+
+/// heartbeat supervision of a module
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchdogLayout {
+    /// a module without `/get` frame for this long is offline
+    pub timeout_ms: u64,
+    /// command sent with `poll_value` while the module is offline to request its state
+    #[serde(default)]
+    pub poll_command: Option<CommandRef>,
+    #[serde(default)]
+    pub poll_value: u16,
+}
+
+/// command whose goal is reached once a decoded `/get` value meets `done`,
+/// the goal value is sent as command value
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationLayout {
+    pub name: String,
+    pub command: u8,
+    /// command sent with `stop_value` when the goal is canceled or times out
+    #[serde(default)]
+    pub stop_command: Option<u8>,
+    #[serde(default)]
+    pub stop_value: u16,
+    pub timeout_ms: u64,
+    pub done: CompletionLayout,
+}
+
+/// compare the decoded value of `field` (a bit field target or `state`) with the goal,
+/// or with `value` when given
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompletionLayout {
+    pub field: String,
+    pub compare: Comparison,
+    #[serde(default)]
+    pub value: Option<i64>,
+    /// allowed distance for `near`
+    #[serde(default)]
+    pub tolerance: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Equal,
+    AtLeast,
+    AtMost,
+    Near,
+}
+
+///////////////////////////////////////////////////////////
+//                   Struct Implementation             ////
+///////////////////////////////////////////////////////////
+
+impl ModuleLayouts {
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               construction                                             ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        let layouts: ModuleLayouts = toml::from_str(content)?;
+        layouts.validate()?;
+        Ok(layouts)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("cannot read module layout file '{}'", path.display()))?;
+        Self::from_toml(&content)
+            .with_context(|| format!("invalid module layout file '{}'", path.display()))
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               class functions                                          ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn get(&self, name: &str) -> Result<&ModuleLayout, Error> {
+        self.module
+            .iter()
+            .find(|layout| layout.name == name)
+            .ok_or_else(|| anyhow!("module layout '{}' is not defined", name))
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut names = HashSet::new();
+        for layout in &self.module {
+            if !names.insert(layout.name.as_str()) {
+                bail!("module '{}' is defined more than once", layout.name);
+            }
+            layout.validate().with_context(|| format!("[{}] invalid layout", layout.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl ModuleLayout {
+    pub fn validate(&self) -> Result<(), Error> {
+        self.input.validate().context("input")?;
+        self.output.validate().context("output")?;
+        if self.input.header.content() != self.output.header.content() {
+            bail!(
+                "input header '{}' and output header '{}' differ",
+                self.input.header.content(), self.output.header.content()
+            );
+        }
+
+        for state in &self.error_states {
+            if *state as u64 > self.max_state() {
+                bail!("error state {} does not fit the state field, the maximum is {}", state, self.max_state());
+            }
+        }
+
+        let mut names = HashSet::new();
+        let mut codes = HashSet::new();
+        for command in &self.command {
+            if !names.insert(command.name.as_str()) {
+                bail!("command '{}' is defined more than once", command.name);
+            }
+            if !codes.insert(command.code) {
+                bail!("command code {} is used more than once", command.code);
+            }
+            command
+                .validate(self.max_command(), self.max_value())
+                .with_context(|| format!("command '{}'", command.name))?;
+            if let Some(state) = command.state {
+                if state as u64 > self.max_state() {
+                    bail!("state {} of command '{}' does not fit the state field, the maximum is {}", state, command.name, self.max_state());
+                }
+                if self.error_states.contains(&state) {
+                    bail!("state {} of command '{}' is an error state", state, command.name);
+                }
+            }
+        }
+
+        let mut names = HashSet::new();
+        for operation in &self.operation {
+            if !names.insert(operation.name.as_str()) {
+                bail!("operation '{}' is defined more than once", operation.name);
+            }
+            self.validate_operation(operation)
+                .with_context(|| format!("operation '{}'", operation.name))?;
+        }
+
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.poll(self).context("watchdog")?;
+        }
+        Ok(())
+    }
+
+    /// check that `operation` fits the command field and waits for a decoded field of this module
+    pub fn validate_operation(&self, operation: &OperationLayout) -> Result<(), Error> {
+        operation.validate(&self.fields(), self.max_command())?;
+
+        let commands = [("command", Some(operation.command)), ("stop_command", operation.stop_command)];
+        for (name, code) in commands {
+            if let Some(code) = code.filter(|code| !self.command.is_empty() && self.command_by_code(*code).is_none()) {
+                bail!("{} {} is not in the command catalog", name, code);
+            }
+        }
+        Ok(())
+    }
+
+    /// names of the decoded values, the bit field targets and `state`
+    pub fn fields(&self) -> HashSet<&str> {
+        let mut fields: HashSet<&str> = self.output.payload
+            .iter()
+            .flat_map(|field| field.bits.iter().map(BitFieldLayout::target))
+            .collect();
+        fields.insert("state");
+        fields
+    }
+
+    pub fn command_by_code(&self, code: u8) -> Option<&CommandLayout> {
+        self.command.iter().find(|command| command.code == code)
+    }
+
+    /// largest command number the command field can hold
+    pub fn max_command(&self) -> u64 {
+        10u64.saturating_pow(self.input.command.size as u32) - 1
+    }
+
+    /// largest value the value field can hold
+    pub fn max_value(&self) -> u64 {
+        10u64.saturating_pow(self.input.value.size as u32) - 1
+    }
+
+    /// largest `state` the state field can hold
+    pub fn max_state(&self) -> u64 {
+        (10u64.saturating_pow(self.output.state.size as u32) - 1).min(u8::MAX as u64)
+    }
+
+    pub fn operation(&self, name: &str) -> Option<&OperationLayout> {
+        self.operation.iter().find(|operation| operation.name == name)
+    }
+
+    pub fn input_format(&self) -> ModuleInputFormat {
+        let input = &self.input;
+        ModuleInputFormat::new(
+            ModuleHead::new(
+                input.header.data_field(),
+                input.package.data_field(),
+                input.setting.data_field(),
+                input.length.data_field()
+            ),
+            input.command.data_field(),
+            input.value.data_field(),
+            ModuleTail::new(
+                input.lrc.data_field(),
+                input.end.data_field()
+            )
+        )
+    }
+
+    pub fn output_format(&self) -> ModuleOutputFormat {
+        let output = &self.output;
+        ModuleOutputFormat::new(
+            ModuleHead::new(
+                output.header.data_field(),
+                output.package.data_field(),
+                output.setting.data_field(),
+                output.length.data_field()
+            ),
+            output.state.data_field(),
+            output.payload.iter().map(PayloadFieldLayout::data_field).collect(),
+            ModuleTail::new(
+                output.lrc.data_field(),
+                output.end.data_field()
+            )
+        )
+    }
+}
+
+impl InputLayout {
+    fn validate(&self) -> Result<(), Error> {
+        validate_sequence(&[
+            ("header", &self.header),
+            ("package", &self.package),
+            ("setting", &self.setting),
+            ("length", &self.length),
+            ("command", &self.command),
+            ("value", &self.value),
+            ("lrc", &self.lrc),
+            ("end", &self.end),
+        ])?;
+        validate_tail(&self.lrc, &self.end)?;
+        validate_length_field(&self.length, self.command.size + self.value.size)
+    }
+}
+
+impl OutputLayout {
+    fn validate(&self) -> Result<(), Error> {
+        let payload_names: Vec<String> = self.payload
+            .iter()
+            .enumerate()
+            .map(|(i, field)| format!("payload[{}] '{}'", i, field.name))
+            .collect();
+        let payload_fields: Vec<FieldLayout> = self.payload
+            .iter()
+            .map(|field| FieldLayout { index: field.index, size: field.size, value: None })
+            .collect();
+
+        let mut fields: Vec<(&str, &FieldLayout)> = vec![
+            ("header", &self.header),
+            ("package", &self.package),
+            ("setting", &self.setting),
+            ("length", &self.length),
+            ("state", &self.state),
+        ];
+        fields.extend(payload_names.iter().map(String::as_str).zip(payload_fields.iter()));
+        fields.push(("lrc", &self.lrc));
+        fields.push(("end", &self.end));
+        validate_sequence(&fields)?;
+        validate_tail(&self.lrc, &self.end)?;
+
+        let mut targets = HashSet::new();
+        for (name, field) in payload_names.iter().zip(self.payload.iter()) {
+            field.validate(&mut targets).with_context(|| name.clone())?;
+        }
+
+        let payload_size: usize = self.payload.iter().map(|field| field.size).sum();
+        validate_length_field(&self.length, self.state.size + payload_size)
+    }
+}
+
+impl WatchdogLayout {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// check the watchdog against the layout of its module and resolve the poll command
+    pub fn poll(&self, layout: &ModuleLayout) -> Result<Option<(u8, u16)>, Error> {
+        if self.timeout_ms == 0 {
+            bail!("timeout_ms must be greater than 0");
+        }
+        let command = match &self.poll_command {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+        let catalog = CommandCatalog::new(layout);
+        let code = catalog
+            .code(command)
+            .ok_or_else(|| anyhow!("module '{}' has no command {}", layout.name, command))?;
+        if code as u64 > layout.max_command() {
+            bail!("poll_command {} does not fit the command field, the maximum is {}", code, layout.max_command());
+        }
+        if self.poll_value as u64 > layout.max_value() {
+            bail!("poll_value {} does not fit the value field, the maximum is {}", self.poll_value, layout.max_value());
+        }
+        catalog.check(code, self.poll_value)?;
+        Ok(Some((code, self.poll_value)))
+    }
+}
+
+impl CommandLayout {
+    fn default_max() -> u16 {
+        u16::MAX
+    }
+
+    fn validate(&self, max_command: u64, max_value: u64) -> Result<(), Error> {
+        if !is_identifier(&self.name) {
+            bail!("name must start with a lowercase letter and contain only 'a-z', '0-9' and '_'");
+        }
+        if self.code as u64 > max_command {
+            bail!("code {} does not fit the command field, the maximum is {}", self.code, max_command);
+        }
+        if self.min > self.max {
+            bail!("min {} is above max {}", self.min, self.max);
+        }
+        // an omitted max means the whole value field
+        if self.max != u16::MAX && self.max as u64 > max_value {
+            bail!("max {} does not fit the value field, the maximum is {}", self.max, max_value);
+        }
+        Ok(())
+    }
+
+    /// largest accepted value, bounded by the value field
+    pub fn max_value(&self, layout: &ModuleLayout) -> u16 {
+        self.max.min(layout.max_value().min(u16::MAX as u64) as u16)
+    }
+}
+
+impl OperationLayout {
+    /// the name becomes part of the `<namespace>/<name>` topics
+    fn validate(&self, fields: &HashSet<&str>, max_command: u64) -> Result<(), Error> {
+        if !is_identifier(&self.name) {
+            bail!("name must start with a lowercase letter and contain only 'a-z', '0-9' and '_'");
+        }
+        if RESERVED_TOPICS.contains(&self.name.as_str()) {
+            bail!("name is already used by the module topic '{}'", self.name);
+        }
+        let commands = [("command", Some(self.command)), ("stop_command", self.stop_command)];
+        for (name, command) in commands {
+            if let Some(command) = command.filter(|command| *command as u64 > max_command) {
+                bail!("{} {} does not fit the command field, the maximum is {}", name, command, max_command);
+            }
+        }
+        if self.timeout_ms == 0 {
+            bail!("timeout_ms must be greater than 0");
+        }
+        if !fields.contains(self.done.field.as_str()) {
+            bail!("field '{}' is neither 'state' nor a bit field target", self.done.field);
+        }
+        if self.done.tolerance < 0 {
+            bail!("tolerance {} must not be negative", self.done.tolerance);
+        }
+        Ok(())
+    }
+}
+
+impl CompletionLayout {
+    pub fn is_met(&self, current: i64, goal: i64) -> bool {
+        self.compare.matches(current, self.value.unwrap_or(goal), self.tolerance)
+    }
+}
+
+impl Comparison {
+    /// name as written in the layout file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "equal",
+            Comparison::AtLeast => "at_least",
+            Comparison::AtMost => "at_most",
+            Comparison::Near => "near",
+        }
+    }
+
+    /// whether `current` compares to `expected`, `tolerance` only applies to `Near`
+    pub fn matches(&self, current: i64, expected: i64, tolerance: i64) -> bool {
+        match self {
+            Comparison::Equal => current == expected,
+            Comparison::AtLeast => current >= expected,
+            Comparison::AtMost => current <= expected,
+            Comparison::Near => current.abs_diff(expected) <= tolerance.unsigned_abs(),
+        }
+    }
+}
+
+impl FieldLayout {
+    /// fixed content, or a zero filled string of the field size
+    pub fn content(&self) -> String {
+        self.value.clone().unwrap_or_else(|| "0".repeat(self.size))
+    }
+
+    pub fn data_field(&self) -> ModuleDataField {
+        ModuleDataField::new(self.size, self.index, self.content())
+    }
+}
+
+impl PayloadFieldLayout {
+    pub fn data_field(&self) -> ModuleDataField {
+        ModuleDataField::new_payload(
+            self.size,
+            self.index,
+            "0".repeat(self.size),
+            self.signed,
+            self.bits.iter().map(BitFieldLayout::bit_field).collect()
+        )
+    }
+
+    /// bit fields must fit the field value without overlapping each other
+    fn validate<'a>(&'a self, targets: &mut HashSet<&'a str>) -> Result<(), Error> {
+        let data_field = self.data_field();
+        if data_field.digits() == 0 || data_field.digits() > 9 {
+            bail!("{} digits cannot be decoded, use 1 to 9 digits", data_field.digits());
+        }
+
+        let capacity = data_field.bit_capacity();
+        let mut used_bits: u64 = 0;
+        for bit in &self.bits {
+            if bit.width == 0 || bit.width > 32 {
+                bail!("bit field '{}' width {} must be in range 1..=32", bit.name, bit.width);
+            }
+            if bit.offset + bit.width > capacity {
+                bail!(
+                    "bit field '{}' bits {}..{} exceed the {} bits of the field",
+                    bit.name, bit.offset, bit.offset + bit.width, capacity
+                );
+            }
+            let bits = ((1u64 << bit.width) - 1) << bit.offset;
+            if used_bits & bits != 0 {
+                bail!("bit field '{}' overlaps another bit field", bit.name);
+            }
+            used_bits |= bits;
+            if !targets.insert(bit.target()) {
+                bail!("bit field '{}' target '{}' is written more than once", bit.name, bit.target());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BitFieldLayout {
+    pub fn target(&self) -> &str {
+        self.target.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn bit_field(&self) -> BitField {
+        BitField::new(self.name.clone(), self.offset, self.width, self.signed, self.target().to_string())
+    }
+}
+
+/// lowercase name usable as ROS topic token
+fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// fields must start at index 0 and follow each other without gap or overlap
+fn validate_sequence(fields: &[(&str, &FieldLayout)]) -> Result<(), Error> {
+    let mut expected_index = 0;
+    for (name, field) in fields {
+        if field.size == 0 {
+            bail!("{} has size 0", name);
+        }
+        if let Some(value) = &field.value {
+            if value.len() != field.size {
+                bail!("{} value '{}' does not match its size {}", name, value, field.size);
+            }
+        }
+        if field.index < expected_index {
+            bail!("{} at index {} overlaps the previous field ending at {}", name, field.index, expected_index);
+        }
+        if field.index > expected_index {
+            bail!("{} at index {} leaves a gap after the previous field ending at {}", name, field.index, expected_index);
+        }
+        expected_index = field.index + field.size;
+    }
+    Ok(())
+}
+
+/// frames end with a 2 digit hex lrc and a single end marker character
+fn validate_tail(lrc: &FieldLayout, end: &FieldLayout) -> Result<(), Error> {
+    if lrc.size != 2 {
+        bail!("lrc has size {} but the lrc is 2 hex digits", lrc.size);
+    }
+    if end.size != 1 {
+        bail!("end has size {} but the end marker is 1 character", end.size);
+    }
+    Ok(())
+}
+
+/// the length field counts the characters between itself and the lrc
+fn validate_length_field(length: &FieldLayout, data_size: usize) -> Result<(), Error> {
+    let content = length.content();
+    let declared = content
+        .parse::<usize>()
+        .map_err(|_| anyhow!("length '{}' is not a decimal number", content))?;
+    if declared != data_size {
+        bail!("length '{}' does not match the data size {}", content, data_size);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUT: &str = r##"
+        [[module]]
+        name = "coffee_feeder"
+
+        [module.input]
+        header  = { index = 0,  size = 4, value = "@COF" }
+        package = { index = 4,  size = 2, value = "00" }
+        setting = { index = 6,  size = 2, value = "00" }
+        length  = { index = 8,  size = 2, value = "07" }
+        command = { index = 10, size = 2 }
+        value   = { index = 12, size = 5 }
+        lrc     = { index = 17, size = 2 }
+        end     = { index = 19, size = 1, value = "#" }
+
+        [module.output]
+        header  = { index = 0,  size = 4, value = "@COF" }
+        package = { index = 4,  size = 2, value = "00" }
+        setting = { index = 6,  size = 2, value = "00" }
+        length  = { index = 8,  size = 2, value = "06" }
+        state   = { index = 10, size = 1 }
+        lrc     = { index = 16, size = 2 }
+        end     = { index = 18, size = 1, value = "#" }
+
+        [[module.output.payload]]
+        name = "status"
+        index = 11
+        size = 5
+        bits = [
+            { name = "capsule", offset = 0, width = 2 },
+            { name = "water_level", offset = 2, width = 2 },
+        ]
+    "##;
+
+    fn error_of(content: &str) -> String {
+        match ModuleLayouts::from_toml(content) {
+            Ok(_) => panic!("layout was accepted"),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    #[test]
+    fn shipped_layouts_are_valid() {
+        let layouts = ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap();
+        for name in ["coffee_feeder", "capsule_feeder", "cup_holder", "light", "pdu", "Tank"] {
+            assert!(layouts.get(name).is_ok(), "module '{}' is missing", name);
+        }
+    }
+
+    #[test]
+    fn accepts_layout() {
+        let layouts = ModuleLayouts::from_toml(LAYOUT).unwrap();
+        let layout = layouts.get("coffee_feeder").unwrap();
+        assert_eq!(layout.max_command(), 99);
+        assert_eq!(layout.max_value(), 99999);
+        assert!(layout.fields().contains("water_level"));
+    }
+
+    #[test]
+    fn rejects_gap_and_overlap() {
+        let gap = LAYOUT.replace("value   = { index = 12, size = 5 }", "value   = { index = 13, size = 4 }");
+        assert!(error_of(&gap).contains("leaves a gap"));
+        let overlap = LAYOUT.replace("value   = { index = 12, size = 5 }", "value   = { index = 11, size = 6 }");
+        assert!(error_of(&overlap).contains("overlaps"));
+    }
+
+    #[test]
+    fn rejects_wrong_length_field() {
+        let content = LAYOUT.replace(r#"size = 2, value = "06""#, r#"size = 2, value = "05""#);
+        assert!(error_of(&content).contains("length"));
+    }
+
+    #[test]
+    fn rejects_different_headers() {
+        let content = LAYOUT.replacen(r#"value = "@COF""#, r#"value = "@CUP""#, 1);
+        assert!(error_of(&content).contains("header"));
+    }
+
+    #[test]
+    fn rejects_overlapping_bit_fields() {
+        let content = LAYOUT.replace(
+            r#"{ name = "water_level", offset = 2, width = 2 }"#,
+            r#"{ name = "water_level", offset = 1, width = 2 }"#,
+        );
+        assert!(error_of(&content).contains("overlaps another bit field"));
+    }
+
+    #[test]
+    fn rejects_bit_fields_beyond_the_field() {
+        let content = LAYOUT.replace(
+            r#"{ name = "water_level", offset = 2, width = 2 }"#,
+            r#"{ name = "water_level", offset = 16, width = 2 }"#,
+        );
+        assert!(error_of(&content).contains("exceed"));
+    }
+
+    #[test]
+    fn rejects_other_tail_sizes() {
+        let lrc = LAYOUT.replace(
+            r##"lrc     = { index = 16, size = 2 }
+        end     = { index = 18, size = 1, value = "#" }"##,
+            r##"lrc     = { index = 16, size = 3 }
+        end     = { index = 19, size = 1, value = "#" }"##,
+        );
+        assert!(error_of(&lrc).contains("lrc has size 3"));
+        let end = LAYOUT.replace(
+            r##"end     = { index = 19, size = 1, value = "#" }"##,
+            r###"end     = { index = 19, size = 2, value = "##" }"###,
+        );
+        assert!(error_of(&end).contains("end has size 2"));
+    }
+
+    #[test]
+    fn rejects_duplicate_modules() {
+        let content = format!("{}{}", LAYOUT, LAYOUT);
+        assert!(error_of(&content).contains("more than once"));
+    }
+
+    #[test]
+    fn rejects_duplicate_command_codes() {
+        let content = format!(
+            "{}{}",
+            LAYOUT,
+            r#"
+            [[module.command]]
+            name = "stop"
+            code = 0
+
+            [[module.command]]
+            name = "halt"
+            code = 0
+            "#
+        );
+        assert!(error_of(&content).contains("command code 0 is used more than once"));
+    }
+
+    #[test]
+    fn rejects_command_states_the_module_cannot_acknowledge() {
+        let with_error_states = LAYOUT.replacen(
+            r#"name = "coffee_feeder""#,
+            "name = \"coffee_feeder\"\n        error_states = [8]",
+            1,
+        );
+        let command = |state: u8| format!(
+            "{}\n[[module.command]]\nname = \"fill_water\"\ncode = 1\nstate = {}\n",
+            with_error_states, state
+        );
+        assert!(ModuleLayouts::from_toml(&command(1)).is_ok());
+        assert!(error_of(&command(8)).contains("state 8 of command 'fill_water' is an error state"));
+        assert!(error_of(&command(10)).contains("does not fit the state field, the maximum is 9"));
+
+        let content = with_error_states.replace("error_states = [8]", "error_states = [12]");
+        assert!(error_of(&content).contains("error state 12 does not fit the state field"));
+    }
+}
@@ -1,27 +1,181 @@
-use anyhow::Error;
-
-pub use crate::module_struct::{ModuleDataField, ModuleOutputFormat, ModuleInputFormat};
-
-pub mod coffee_feeder_converter;
-pub mod capsule_feeder_converter;
-pub mod cup_holder_converter;
-pub mod tank_converter;
-pub mod pdu_converter;
-pub mod light_converter;
+use anyhow::{anyhow, Error};
+use std::sync::atomic::AtomicBool;
+
+use crate::command_tracker::CommandError;
+use crate::module_layout::OperationLayout;
+use crate::module_operation::OperationError;
+
+pub use crate::module_struct::{ModuleDataField, ModuleHead, ModuleOutputFormat, ModuleInputFormat, ModuleTail};
+
+pub mod decode_error;
+pub mod encode_error;
+pub mod module_converter;
+pub mod module_msgs;
+pub mod module_srvs;
+
+pub use decode_error::DecodeError;
+pub use encode_error::EncodeError;
+pub use module_converter::ModuleConverter;
+
+use obd_coffee_maker_interface::msg::{
+    CoffeeFeederInput, CoffeeFeederOutput,
+    CapsuleFeederInput, CapsuleFeederOutput,
+    CupHolderInput, CupHolderOutput,
+    TankInput, TankOutput,
+    PDUInput, PDUOutput,
+    LightInput, LightOutput};
+use obd_coffee_maker_interface::srv::{
+    CoffeeFeederCommand, CapsuleFeederCommand, CupHolderCommand,
+    TankCommand, PDUCommand, LightCommand,
+    CoffeeFeederOperation, CapsuleFeederOperation, CupHolderOperation,
+    TankOperation, PDUOperation, LightOperation};
+
+pub type CoffeeFeederConverter = ModuleConverter<CoffeeFeederInput, CoffeeFeederOutput, CoffeeFeederCommand, CoffeeFeederOperation>;
+pub type CapsuleFeederConverter = ModuleConverter<CapsuleFeederInput, CapsuleFeederOutput, CapsuleFeederCommand, CapsuleFeederOperation>;
+pub type CupHolderConverter = ModuleConverter<CupHolderInput, CupHolderOutput, CupHolderCommand, CupHolderOperation>;
+pub type TankConverter = ModuleConverter<TankInput, TankOutput, TankCommand, TankOperation>;
+pub type PDUConverter = ModuleConverter<PDUInput, PDUOutput, PDUCommand, PDUOperation>;
+pub type LightConverter = ModuleConverter<LightInput, LightOutput, LightCommand, LightOperation>;
 
 pub trait Converter {
     type ModuleInput;
     type ModuleOutput;
 
+    fn name(&self) -> &str;
+
     fn start(&self) -> Result<(), Error>;
 
-    fn ros_2_mqtt(&self, ros_msg: &Self::ModuleInput) -> String;
+    fn ros_2_mqtt(&self, ros_msg: &Self::ModuleInput) -> Result<String, EncodeError>;
+
+    fn mqtt_2_ros(&self, mqtt_msg: &str) -> Result<Self::ModuleOutput, DecodeError>;
+
+    /// encode `ros_msg` into the `/get` frame the module would send, the inverse of `mqtt_2_ros`
+    fn encode_output(&self, ros_msg: &Self::ModuleOutput) -> Result<String, EncodeError>;
+
+    /// send the `/set` frame of `ros_msg` and wait for the module to acknowledge it,
+    /// returns the decoded acknowledging `/get` frame
+    fn send_command(&self, ros_msg: &Self::ModuleInput) -> Result<Self::ModuleOutput, CommandError>;
+
+    /// send a command and wait for the module to acknowledge it
+    fn execute_command(&self, command: u8, value: u16) -> Result<Self::ModuleOutput, CommandError>;
+
+    /// `execute_command` that gives up once `canceled` is set
+    fn execute_command_until(&self, command: u8, value: u16, canceled: &AtomicBool) -> Result<Self::ModuleOutput, CommandError>;
+
+    /// run `operation` with `goal` until it completes, fails or is canceled. `canceled` covers
+    /// a cancel that arrives before the operation started and cannot reach it by name
+    fn run_operation(
+        &self,
+        operation: &OperationLayout,
+        goal: u16,
+        canceled: &AtomicBool,
+    ) -> Result<Self::ModuleOutput, OperationError>;
 
-    fn mqtt_2_ros(&self, mqtt_msg: &str) -> Option<Self::ModuleOutput>;
+    /// cancel `operation` if it is running
+    fn cancel_operation(&self, operation: &str) -> bool;
 
-    fn handle_mqtt_message(&self, topic: &str, payload: &str);
+    /// decode a `/get` frame of this module and publish it to ROS
+    fn handle_frame(&self, frame: &str) -> Result<(), DecodeError>;
 }
 
+/// ROS input message encoded into a `/set` frame
+pub trait ModuleInputMsg {
+    fn command(&self) -> u8;
+
+    fn value(&self) -> u16;
+}
+
+/// ROS output message filled by the generic bit field decoder
+pub trait ModuleOutputMsg: Default {
+    fn state(&self) -> u8;
+
+    fn set_state(&mut self, state: u8);
+
+    /// write a decoded bit field to the message field `target`, `index` addresses
+    /// an element of a list field
+    fn set_field(&mut self, target: &str, index: Option<usize>, value: i64) -> Result<(), DecodeError>;
+
+    /// read the message field `target` for the bit field encoder, the inverse of `set_field`
+    fn field(&self, target: &str, index: Option<usize>) -> Result<i64, EncodeError>;
+}
+
+/// `<namespace>/command` service of a module, defined in the interface package as
+/// `uint8 command` `uint16 value` `---` `bool success` `string message` `<Module>Output output`
+pub trait ModuleCommandSrv: rosidl_runtime_rs::Service {
+    type ModuleOutput;
+
+    fn command(request: &Self::Request) -> u8;
+
+    fn value(request: &Self::Request) -> u16;
+
+    /// `message` carries the reason of a failure
+    fn response(success: bool, message: String, output: Self::ModuleOutput) -> Self::Response;
+}
+
+/// `<namespace>/<operation>` goal service of a module, defined in the interface package as
+/// `uint16 goal` `---` `bool success` `string message` `<Module>Output output`
+pub trait ModuleOperationSrv: rosidl_runtime_rs::Service {
+    type ModuleOutput;
+
+    fn goal(request: &Self::Request) -> u16;
+
+    /// `message` carries the reason of a failure
+    fn response(success: bool, message: String, output: Self::ModuleOutput) -> Self::Response;
+}
+
+/// convert a decoded bit field value to the type of its target field
+pub fn field_value<T: TryFrom<i64>>(target: &str, value: i64) -> Result<T, DecodeError> {
+    T::try_from(value).map_err(|_| DecodeError::OutOfRange { field: target.to_string(), value })
+}
+
+/// unknown field of an output message
+pub fn unknown_field(target: &str) -> EncodeError {
+    EncodeError::UnknownField { field: target.to_string() }
+}
+
+/// split a bit field target like `capsule_status_list[2]` into field name and index
+pub fn split_target(target: &str) -> (&str, Option<usize>) {
+    if let Some(open) = target.find('[') {
+        if let Some(index) = target[open + 1..].strip_suffix(']') {
+            if let Ok(index) = index.parse::<usize>() {
+                return (&target[..open], Some(index));
+            }
+        }
+    }
+    (target, None)
+}
+
+/// state and bit field values of a `/get` frame, keyed by bit field target
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodedValues {
+    pub state: u8,
+    pub fields: Vec<(String, i64)>,
+}
+
+impl DecodedValues {
+    /// value of a bit field target, `state` addresses the state field
+    pub fn get(&self, target: &str) -> Option<i64> {
+        if target == "state" {
+            return Some(self.state as i64);
+        }
+        self.fields
+            .iter()
+            .find(|(name, _)| name == target)
+            .map(|(_, value)| *value)
+    }
+
+    /// change the value of a bit field target, `state` addresses the state field
+    pub fn set(&mut self, target: &str, value: i64) {
+        if target == "state" {
+            self.state = value.clamp(0, u8::MAX as i64) as u8;
+            return;
+        }
+        match self.fields.iter_mut().find(|(name, _)| name == target) {
+            Some((_, current)) => *current = value,
+            None => self.fields.push((target.to_string(), value)),
+        }
+    }
+}
 
 pub struct ModuleMsgConverter {
     // node: Node,
@@ -74,35 +228,211 @@ impl ModuleMsgConverter {
     ////////////////////////////////////////////////////////////////////////////////
     ////               class functions                                          ////
     ////////////////////////////////////////////////////////////////////////////////
-    pub fn payload_to_binary_string(&self, payload: &str) -> String {
-        assert_eq!(payload.len(), 5, "payload length must be 5 char but input is '{:?}'", payload);
-        format!("{:016b}", payload.parse::<u16>().unwrap()).chars().rev().collect::<String>()
+    /// check that every bit field of the output format targets an existing field of `T`
+    pub fn validate_targets<T: ModuleOutputMsg>(&self) -> Result<(), Error> {
+        let mut output = T::default();
+        for field in &self.output_format.payload {
+            for bit_field in &field.bit_fields {
+                let (target, index) = split_target(&bit_field.target);
+                if let Err(e) = output.set_field(target, index, 0) {
+                    return Err(anyhow!(
+                        "[{}] bit field '{}' cannot be decoded: {}",
+                        self.module_name, bit_field.name, e
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// validate a `/get` string and decode it into `T`
+    pub fn decode_get_str<T: ModuleOutputMsg>(&self, mqtt_msg: &str) -> Result<T, DecodeError> {
+        self.validate_get_str(mqtt_msg)?;
+        self.decode_output(mqtt_msg)
+    }
+
+    /// validate a `/get` string and decode it into the values of its bit field targets
+    pub fn decode_get_values(&self, mqtt_msg: &str) -> Result<DecodedValues, DecodeError> {
+        self.validate_get_str(mqtt_msg)?;
+        self.decode_values(mqtt_msg)
+    }
+
+    /// validate a `/set` string and decode its command and value
+    pub fn decode_set_str(&self, mqtt_msg: &str) -> Result<(u8, u16), DecodeError> {
+        self.validate_frame(mqtt_msg, &self.input_format.head, &self.input_format.tail, self.input_pkg_length())?;
+        let command = self.field_str(mqtt_msg, &self.input_format.command)?;
+        let command = Self::parse_field::<u8>("command", command)?;
+        let value = self.field_str(mqtt_msg, &self.input_format.value)?;
+        let value = Self::parse_field::<u16>("value", value)?;
+        Ok((command, value))
+    }
+
+    /// encode the complete `/set` string of a command, the inverse of `decode_set_str`
+    pub fn encode_set_str(&self, command: u8, value: u16) -> Result<String, EncodeError> {
+        let content = self.create_module_set_message(command, value)?;
+        let lrc = self.calculate_lrc_from_string(&content);
+        Ok(format!("{}{}{}", content, lrc, self.input_format.end().string))
+    }
+
+    fn parse_field<T: TryFrom<u64>>(name: &str, digits: &str) -> Result<T, DecodeError> {
+        let raw = parse_digits::<u64>(digits)
+            .ok_or_else(|| DecodeError::NonNumericField { field: name.to_string(), value: digits.to_string() })?;
+        T::try_from(raw).map_err(|_| DecodeError::OutOfRange { field: name.to_string(), value: raw as i64 })
+    }
+
+    /// encode `T` into the complete `/get` frame following the bit fields of the output format
+    pub fn encode_output<T: ModuleOutputMsg>(&self, msg: &T) -> Result<String, EncodeError> {
+        let mut values = DecodedValues { state: msg.state(), fields: Vec::new() };
+        for field in &self.output_format.payload {
+            for bit_field in &field.bit_fields {
+                let (target, index) = split_target(&bit_field.target);
+                values.fields.push((bit_field.target.clone(), msg.field(target, index)?));
+            }
+        }
+        self.encode_values(&values)
     }
 
-    pub fn binary_string_to_int(&self, bin_str: &str) -> u16 {
-        let rev_str = bin_str.chars().rev().collect::<String>();
-        u16::from_str_radix(rev_str.as_str(), 2).unwrap()
+    /// decode a validated `/get` string into `T` following the bit fields of the output format
+    pub fn decode_output<T: ModuleOutputMsg>(&self, mqtt_msg: &str) -> Result<T, DecodeError> {
+        let values = self.decode_values(mqtt_msg)?;
+
+        let mut output = T::default();
+        output.set_state(values.state);
+        for (target, value) in &values.fields {
+            let (target, index) = split_target(target);
+            output.set_field(target, index, *value)?;
+        }
+
+        Ok(output)
     }
 
-    pub fn create_module_set_message<T: Into<u16>>(&self, cmd_int: u8, val_int: T) -> String {
-        format!(
+    /// decode a validated `/get` string into the values of its bit field targets
+    pub fn decode_values(&self, mqtt_msg: &str) -> Result<DecodedValues, DecodeError> {
+        let state = &self.output_format.state;
+        let state_str = self.field_str(mqtt_msg, state)?;
+        let mut values = match parse_digits::<u8>(state_str) {
+            Some(num) => DecodedValues { state: num, fields: Vec::new() },
+            None => {
+                return Err(DecodeError::NonNumericField { field: "state".to_string(), value: state_str.to_string() });
+            }
+        };
+
+        for field in &self.output_format.payload {
+            let field_str = self.field_str(mqtt_msg, field)?;
+            // signed field replace the first character('0' or '-') by the sign
+            let (negative, digits) = if field.signed {
+                (field_str.starts_with('-'), field_str.get(1..).unwrap_or_default())
+            } else {
+                (false, field_str)
+            };
+
+            let raw = match parse_digits::<u32>(digits) {
+                Some(raw) => raw,
+                None => {
+                    return Err(DecodeError::NonNumericField { field: format!("payload at index {}", field.index), value: field_str.to_string() });
+                }
+            };
+
+            for bit_field in &field.bit_fields {
+                let mut value = bit_field.extract(raw);
+                if negative {
+                    value = -value;
+                }
+                values.fields.push((bit_field.target.clone(), value));
+            }
+        }
+
+        Ok(values)
+    }
+
+    pub fn create_module_set_message<T: Into<u16>>(&self, cmd_int: u8, val_int: T) -> Result<String, EncodeError> {
+        let val_int = val_int.into();
+        // zero padding only widens, a wider number would shift every following field
+        Self::check_field_width("command", cmd_int as u64, &self.input_format.command)?;
+        Self::check_field_width("value", val_int as u64, &self.input_format.value)?;
+
+        Ok(format!(
             "{}{}{}{}{:0cmd_size$}{:0val_size$}",
             self.input_format.header().string,
             self.input_format.package().string,
             self.input_format.setting().string,
             self.input_format.length().string,
-            cmd_int, val_int.into(),
+            cmd_int, val_int,
             cmd_size = self.input_format.command.size,
             val_size = self.input_format.value.size
-        )
+        ))
+    }
+
+    /// build the complete `/get` frame of `values`, bit field targets missing from `values` are sent as 0
+    pub fn encode_values(&self, values: &DecodedValues) -> Result<String, EncodeError> {
+        Self::check_field_width("state", values.state as u64, &self.output_format.state)?;
+        let mut content = format!(
+            "{}{}{}{}{:0state_size$}",
+            self.output_format.header().string,
+            self.output_format.package().string,
+            self.output_format.setting().string,
+            self.output_format.length().string,
+            values.state,
+            state_size = self.output_format.state.size
+        );
+        for field in &self.output_format.payload {
+            content.push_str(&Self::encode_payload_field(field, values)?);
+        }
+
+        let lrc = self.calculate_lrc_from_string(&content);
+        Ok(format!("{}{}{}", content, lrc, self.output_format.end().string))
+    }
+
+    /// inverse of the payload loop of `decode_values`
+    fn encode_payload_field(field: &ModuleDataField, values: &DecodedValues) -> Result<String, EncodeError> {
+        let mut negative = false;
+        let mut raw: u64 = 0;
+        for bit_field in &field.bit_fields {
+            let mut value = values.get(&bit_field.target).unwrap_or(0);
+            // the sign of a signed field applies to all of its bit fields
+            if field.signed && value < 0 {
+                negative = true;
+                value = -value;
+            }
+            raw |= bit_field.insert(value).ok_or_else(|| EncodeError::BitFieldOverflow {
+                target: bit_field.target.clone(),
+                value,
+                width: bit_field.width,
+            })?;
+        }
+
+        let name = format!("payload at index {}", field.index);
+        if raw.to_string().len() > field.digits() {
+            return Err(EncodeError::FieldOverflow { field: name, value: raw, size: field.digits() });
+        }
+        if field.signed {
+            Ok(format!("{}{:0digits$}", if negative { '-' } else { '0' }, raw, digits = field.digits()))
+        } else {
+            Ok(format!("{:0size$}", raw, size = field.size))
+        }
+    }
+
+    fn check_field_width(name: &str, value: u64, field: &ModuleDataField) -> Result<(), EncodeError> {
+        if value.to_string().len() > field.size {
+            return Err(EncodeError::FieldOverflow { field: name.to_string(), value, size: field.size });
+        }
+        Ok(())
     }
 
-    pub fn payload_from_full_output_format_string(&self, output_format_string: &str) -> String {
+    pub fn payload_from_full_output_format_string(&self, output_format_string: &str) -> Result<String, DecodeError> {
         let idx_start = self.output_format.state.index;
         let idx_end = self.output_format.lrc().index;
-        output_format_string[idx_start..idx_end].to_string()
+        match output_format_string.get(idx_start..idx_end) {
+            Some(payload) => Ok(payload.to_string()),
+            None => Err(DecodeError::LengthMismatch { expected: self.output_pkg_length(), actual: output_format_string.len() }),
+        }
     } //output_mqtt_msg still valid after call this function hence borrow it
 
+    fn field_str<'a>(&self, msg: &'a str, field: &ModuleDataField) -> Result<&'a str, DecodeError> {
+        msg.get(field.index..field.index + field.size)
+            .ok_or(DecodeError::LengthMismatch { expected: self.output_pkg_length(), actual: msg.len() })
+    }
+
     fn calculate_lrc_from_string(&self, data: &str) -> String {
         // Step 1: Sum the ASCII values of the characters
         let sum_val: u32 = data.bytes().map(|b| b as u32).sum();
@@ -131,55 +461,227 @@ impl ModuleMsgConverter {
         format!("{}{}", lrc_0, lrc_1)
     }
 
-    fn validate_get_str(&self, msg: &str) -> bool {
-        if self.output_pkg_length() != msg.len() {
-            eprintln!("{}", &format!( 
-                "[{}] {} format incorrect with output format [{}/{}]",
-                self.module_name, msg, self.output_pkg_length(), msg.len()
-            ));
-            return false;
+    fn validate_get_str(&self, msg: &str) -> Result<(), DecodeError> {
+        self.validate_frame(msg, &self.output_format.head, &self.output_format.tail, self.output_pkg_length())
+    }
+
+    /// check the framing of `/get` and `/set` strings, `head` and `tail` come from their format
+    fn validate_frame(&self, msg: &str, head: &ModuleHead, tail: &ModuleTail, length: usize) -> Result<(), DecodeError> {
+        // Step 1: Check characters and length, every index below is a byte index
+        if let Some((index, found)) = msg.char_indices().find(|(_, c)| !c.is_ascii()) {
+            return Err(DecodeError::InvalidCharacter { index, found });
+        }
+
+        if length != msg.len() {
+            return Err(DecodeError::LengthMismatch { expected: length, actual: msg.len() });
         }
 
-        // Step 2: Extract data and calculate LRC
-        let data: &str = &msg[..msg.len() - 1];
-        let lrc = self.calculate_lrc_from_string(&data[..data.len() - 2]);
+        // Step 2: Check the fixed fields against the format
+        let header = &head.header;
+        let found = self.field_str(msg, header)?;
+        if found != header.string {
+            return Err(DecodeError::WrongHeader { expected: header.string.clone(), found: found.to_string() });
+        }
 
-        // Extract the second last and last characters
-        let second_last_char = data.chars().nth(data.len() - 2).unwrap();
-        let last_char = data.chars().nth(data.len() - 1).unwrap();
+        let fixed_fields = [
+            ("package", &head.package),
+            ("setting", &head.setting),
+            ("length", &head.length),
+        ];
+        for (name, field) in fixed_fields {
+            let found = self.field_str(msg, field)?;
+            if found != field.string {
+                return Err(DecodeError::FieldMismatch {
+                    field: name.to_string(),
+                    expected: field.string.clone(),
+                    found: found.to_string(),
+                });
+            }
+        }
 
-        // Convert the second last character to its integer value
-        let mut buf = if second_last_char < 'A' {
-            (second_last_char as u8 - b'0') << 4
-        } else {
-            (second_last_char as u8 - b'A' + 10) << 4
-        };
+        let end = &tail.end;
+        let found = self.field_str(msg, end)?;
+        if found != end.string {
+            return Err(DecodeError::MissingEndMarker { expected: end.string.clone(), found: found.to_string() });
+        }
 
-        // Convert the last character to its integer value and combine it with the buffer
-        buf |= if last_char < 'A' {
-            last_char as u8 - b'0'
-        } else {
-            last_char as u8 - b'A' + 10
+        // Step 3: Extract data and calculate LRC
+        let lrc_field = &tail.lrc;
+        let content = &msg[..lrc_field.index];
+        let received = self.field_str(msg, lrc_field)?;
+        let lrc = self.calculate_lrc_from_string(content);
+
+        // Step 4: Convert the two LRC characters to their integer value
+        let mut nibbles = received.chars().map(|c| c.to_digit(16));
+        let buf = match (nibbles.next().flatten(), nibbles.next().flatten()) {
+            (Some(high), Some(low)) => (high << 4) | low,
+            _ => {
+                return Err(DecodeError::ChecksumMismatch { received: received.to_string(), calculated: lrc });
+            }
         };
 
-        // Step 4: Create new data string by replacing the last two characters with buffer
-        let new_data = String::from(&data[..data.len() - 2]);
-        // new_data.push(buf as char);
-
         // Step 5: Calculate checksum and validate it
-        let mut sum_val: u32 = new_data.bytes().map(|b| b as u32).sum();
-        sum_val += buf as u32;
+        let mut sum_val: u32 = content.bytes().map(|b| b as u32).sum();
+        sum_val += buf;
 
         let sum_val_8bit: u32 = sum_val & 0xFF;
 
         if sum_val_8bit != 0 {
-            eprintln!("{}", &format!(
-                "[{}] Checksum not validated with input LRC: {} validate is LRC: {}",
-                self.module_name, &msg[msg.len() - 3..msg.len() - 1],
-                lrc
-            ));
+            return Err(DecodeError::ChecksumMismatch {
+                received: received.to_string(),
+                calculated: lrc,
+            });
         }
 
-        sum_val_8bit == 0
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// parse a field made only of decimal digits, `str::parse` would also accept a leading '+'
+fn parse_digits<T: std::str::FromStr>(digits: &str) -> Option<T> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<T>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module_layout::ModuleLayouts;
+
+    fn converter(name: &str) -> ModuleMsgConverter {
+        let layouts = ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap();
+        let layout = layouts.get(name).unwrap();
+        ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format())
+    }
+
+    /// frame with a valid lrc around `content`
+    fn frame(converter: &ModuleMsgConverter, content: &str) -> String {
+        format!("{}{}#", content, converter.calculate_lrc_from_string(content))
+    }
+
+    #[test]
+    fn lrc_is_two_complement_of_the_byte_sum() {
+        let converter = converter("coffee_feeder");
+        assert_eq!(converter.calculate_lrc_from_string("@COF000006100000"), "A1");
+        assert_eq!(converter.calculate_lrc_from_string("@COF000006100005"), "9C");
+        assert_eq!(converter.calculate_lrc_from_string("@COF0000070100002"), "6E");
+    }
+
+    #[test]
+    fn decodes_valid_get_frame() {
+        let values = converter("coffee_feeder").decode_get_values("@COF0000061000059C#").unwrap();
+        assert_eq!(values.state, 1);
+        assert_eq!(values.get("capsule"), Some(1));
+        assert_eq!(values.get("water_level"), Some(1));
+        assert_eq!(values.get("coffee_feeder"), Some(0));
+    }
+
+    #[test]
+    fn rejects_wrong_lrc() {
+        let converter = converter("coffee_feeder");
+        for frame in ["@COF0000061000059D#", "@COF000006100005ZZ#", "@COF000006100005+9#"] {
+            match converter.decode_get_values(frame) {
+                Err(DecodeError::ChecksumMismatch { calculated, .. }) => assert_eq!(calculated, "9C"),
+                other => panic!("{} decoded as {:?}", frame, other),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_broken_framing() {
+        let converter = converter("coffee_feeder");
+        let cases = [
+            ("@COF0000061000059C", "length_mismatch"),
+            ("@COF0000061000059C##", "length_mismatch"),
+            ("@CAP0000061000059C#", "wrong_header"),
+            ("@COF0100061000059C#", "field_mismatch"),
+            ("@COF0000051000059C#", "field_mismatch"),
+            ("@COF0000061000059C$", "missing_end_marker"),
+            ("@COF00000610000é9C#", "invalid_character"),
+        ];
+        for (frame, kind) in cases {
+            match converter.decode_get_values(frame) {
+                Err(e) => assert_eq!(e.kind(), kind, "{}", frame),
+                Ok(values) => panic!("{} decoded as {:?}", frame, values),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_non_numeric_fields() {
+        let converter = converter("coffee_feeder");
+        for content in ["@COF000006X00005", "@COF00000610000x", "@COF0000061+0005"] {
+            match converter.decode_get_values(&frame(&converter, content)) {
+                Err(e) => assert_eq!(e.kind(), "non_numeric_field", "{}", content),
+                Ok(values) => panic!("{} decoded as {:?}", content, values),
+            }
+        }
+    }
+
+    fn get_frame_round_trip<T: ModuleOutputMsg + PartialEq + std::fmt::Debug>(name: &str, msg: T) {
+        let converter = converter(name);
+        let frame = converter.encode_output(&msg).unwrap();
+        assert_eq!(converter.decode_get_str::<T>(&frame).unwrap(), msg, "{}", frame);
+    }
+
+    #[test]
+    fn get_frame_round_trip_of_every_module() {
+        get_frame_round_trip("coffee_feeder", CoffeeFeederOutput {
+            state: 1, capsule: 2, water_level: 3, water_filling: 1, coffee_feeder: 9,
+        });
+        get_frame_round_trip("capsule_feeder", CapsuleFeederOutput {
+            state: 2,
+            capsule_status_list: vec![0, 1, 2, 3, 1, 0],
+            capsule_detect: true,
+            capsule_slot_pos: 5,
+            capsule_selector_pos: 15,
+            home_detect: 1,
+        });
+        // the sign takes one of the five characters, four digits remain
+        for weight in [-9999, -1234, 0, 9999] {
+            get_frame_round_trip("cup_holder", CupHolderOutput {
+                state: 1,
+                coffee_detect: true,
+                cup_detect: false,
+                water_detect: true,
+                ice_detect: false,
+                cup_pump: true,
+                cup_stock: 3,
+                position: 65535,
+                weight,
+            });
+        }
+        get_frame_round_trip("Tank", TankOutput { state: 0, water_quantity: 1500, waste_quantity: 65535 });
+        get_frame_round_trip("pdu", PDUOutput {
+            state: 1,
+            coffee_pwr: true,
+            capsule_pwr: false,
+            cup_pwr: true,
+            tank_pwr: false,
+            light_pwr: true,
+            voltage: 24000,
+            current: 1200,
+        });
+        get_frame_round_trip("light", LightOutput { state: 1 });
+    }
+
+    #[test]
+    fn set_frame_round_trip() {
+        let converter = converter("coffee_feeder");
+        let content = converter.create_module_set_message(1, 2u16).unwrap();
+        assert_eq!(content, "@COF0000070100002");
+        let frame = frame(&converter, &content);
+        assert_eq!(frame, "@COF00000701000026E#");
+        assert_eq!(converter.encode_set_str(1, 2).unwrap(), frame);
+        assert_eq!(converter.decode_set_str(&frame).unwrap(), (1, 2));
+    }
+
+    #[test]
+    fn set_frame_rejects_overflowing_fields() {
+        let converter = converter("coffee_feeder");
+        assert!(converter.create_module_set_message(100, 0u16).is_err());
+        assert!(converter.create_module_set_message(1, 65535u16).is_ok());
+    }
+}
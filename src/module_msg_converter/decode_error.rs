@@ -0,0 +1,77 @@
+use std::fmt;
+
+/// reason a `/get` frame could not be decoded into its ROS output message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// frame contains a non ASCII character
+    InvalidCharacter { index: usize, found: char },
+    /// frame length differs from the output format
+    LengthMismatch { expected: usize, actual: usize },
+    /// frame does not start with the module header tag
+    WrongHeader { expected: String, found: String },
+    /// package, setting or length field differs from the output format
+    FieldMismatch { field: String, expected: String, found: String },
+    /// LRC of the frame does not match its content
+    ChecksumMismatch { received: String, calculated: String },
+    /// state or payload field is not a decimal number
+    NonNumericField { field: String, value: String },
+    /// decoded value does not fit the output message field
+    OutOfRange { field: String, value: i64 },
+    /// frame does not end with the end marker
+    MissingEndMarker { expected: String, found: String },
+    /// bit field targets a field the output message does not have
+    UnknownField { field: String },
+}
+
+impl DecodeError {
+    /// short name of the failure class, stable for counting and logging
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DecodeError::InvalidCharacter { .. } => "invalid_character",
+            DecodeError::LengthMismatch { .. } => "length_mismatch",
+            DecodeError::WrongHeader { .. } => "wrong_header",
+            DecodeError::FieldMismatch { .. } => "field_mismatch",
+            DecodeError::ChecksumMismatch { .. } => "checksum_mismatch",
+            DecodeError::NonNumericField { .. } => "non_numeric_field",
+            DecodeError::OutOfRange { .. } => "out_of_range",
+            DecodeError::MissingEndMarker { .. } => "missing_end_marker",
+            DecodeError::UnknownField { .. } => "unknown_field",
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidCharacter { index, found } => {
+                write!(f, "frame contains non ASCII character {:?} at byte {}", found, index)
+            }
+            DecodeError::LengthMismatch { expected, actual } => {
+                write!(f, "frame length {} does not match output format length {}", actual, expected)
+            }
+            DecodeError::WrongHeader { expected, found } => {
+                write!(f, "frame header '{}' does not match '{}'", found, expected)
+            }
+            DecodeError::FieldMismatch { field, expected, found } => {
+                write!(f, "frame {} '{}' does not match '{}'", field, found, expected)
+            }
+            DecodeError::ChecksumMismatch { received, calculated } => {
+                write!(f, "checksum not validated with input LRC: {} validate is LRC: {}", received, calculated)
+            }
+            DecodeError::NonNumericField { field, value } => {
+                write!(f, "field '{}' value '{}' is not a number", field, value)
+            }
+            DecodeError::OutOfRange { field, value } => {
+                write!(f, "value {} is out of range for field '{}'", value, field)
+            }
+            DecodeError::MissingEndMarker { expected, found } => {
+                write!(f, "frame ends with '{}' instead of end marker '{}'", found, expected)
+            }
+            DecodeError::UnknownField { field } => {
+                write!(f, "output message has no field '{}'", field)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
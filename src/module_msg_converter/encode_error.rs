@@ -0,0 +1,41 @@
+use std::fmt;
+
+/// reason a command or a module state could not be encoded into a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// value has more digits than its frame field
+    FieldOverflow { field: String, value: u64, size: usize },
+    /// value is out of the range of its output format bit field
+    BitFieldOverflow { target: String, value: i64, width: u32 },
+    /// bit field targets a field the output message does not have
+    UnknownField { field: String },
+}
+
+impl EncodeError {
+    /// short name of the failure class, stable for counting and logging
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EncodeError::FieldOverflow { .. } => "field_overflow",
+            EncodeError::BitFieldOverflow { .. } => "bit_field_overflow",
+            EncodeError::UnknownField { .. } => "unknown_field",
+        }
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::FieldOverflow { field, value, size } => {
+                write!(f, "{} {} does not fit the {} character {} field", field, value, size, field)
+            }
+            EncodeError::BitFieldOverflow { target, value, width } => {
+                write!(f, "{} {} does not fit its {} bit field", target, value, width)
+            }
+            EncodeError::UnknownField { field } => {
+                write!(f, "output message has no field '{}'", field)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
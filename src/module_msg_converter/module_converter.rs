@@ -0,0 +1,485 @@
+use crate::command_catalog::CommandCatalog;
+use crate::command_tracker::{CommandError, CommandTracker};
+use crate::config::CommandConfig;
+use crate::interlock::InterlockGuard;
+use crate::module_layout::{ModuleLayout, OperationLayout};
+use crate::module_state::ModuleStates;
+use crate::module_operation::{OperationError, OperationRunner};
+use crate::transport::FrameTransport;
+use super::{
+    Converter, DecodeError, EncodeError, ModuleCommandSrv, ModuleInputMsg, ModuleMsgConverter, ModuleOperationSrv,
+    ModuleOutputMsg,
+};
+use std::{sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, TrySendError}, Arc, Mutex}, time::Instant};
+use anyhow::{Result, Error};
+use log::{debug, error, trace, warn};
+use rclrs::{Node, Publisher, Service, Subscription};
+use obd_coffee_maker_interface::msg::CommandResult as CommandResultMsg;
+use obd_coffee_maker_interface::srv::{NamedCommand, NamedCommand_Request, NamedCommand_Response};
+use std_msgs::msg::Empty as EmptyMsg;
+use rosidl_runtime_rs::{Message, Service as ServiceType};
+
+/// `/input` commands waiting for the worker of their module, further commands are rejected
+const INPUT_QUEUE_CAPACITY: usize = 16;
+
+/// converter of one module, the frame layout and bit fields come from `ModuleLayout`
+/// and the ROS side from the `I`/`O` message types, the `S` command service and the
+/// `G` goal service of its operations
+pub struct ModuleConverter<I: Message, O: Message, S: ServiceType, G: ServiceType> {
+    pub name: String,
+    base_converter: ModuleMsgConverter,
+    node: Arc<Node>,
+    transport: Arc<dyn FrameTransport>,
+    command_tracker: Arc<CommandTracker<O>>,
+    command_catalog: Arc<CommandCatalog>,
+    states: Arc<ModuleStates>,
+    interlock: Arc<InterlockGuard>,
+    pub ros_subscriber: Arc<Mutex<Option<Arc<Subscription<I>>>>>,
+    pub ros_publisher: Arc<Mutex<Option<Arc<Publisher<O>>>>>,
+    pub command_result_publisher: Arc<Mutex<Option<Arc<Publisher<CommandResultMsg>>>>>,
+    pub command_service: Arc<Mutex<Option<Arc<Service<S>>>>>,
+    pub named_command_service: Arc<Mutex<Option<Arc<Service<NamedCommand>>>>>,
+    operations: Vec<OperationLayout>,
+    operation_runner: Arc<OperationRunner<O>>,
+    pub operation_handles: Arc<Mutex<Vec<OperationHandles<O, G>>>>,
+}
+
+/// ROS entities of one operation, see `module_operation` for how they form an action
+pub struct OperationHandles<O: Message, G: ServiceType> {
+    /// `<namespace>/<operation>`, the response tells if the goal was accepted
+    pub goal_service: Arc<Service<G>>,
+    /// `<namespace>/<operation>/feedback`, every decoded frame while the operation runs
+    pub feedback_publisher: Arc<Publisher<O>>,
+    /// `<namespace>/<operation>/result`, published once the operation ended
+    pub result_publisher: Arc<Publisher<G::Response>>,
+    /// `<namespace>/<operation>/cancel`
+    pub cancel_subscription: Arc<Subscription<EmptyMsg>>,
+}
+
+impl<I: Message, O: Message, S: ServiceType, G: ServiceType> ModuleConverter<I, O, S, G> {
+    pub fn new(
+        transport: Arc<dyn FrameTransport>,
+        node: Arc<Node>,
+        layout: &ModuleLayout,
+        command_config: &CommandConfig,
+        states: Arc<ModuleStates>,
+        interlock: Arc<InterlockGuard>
+    ) -> Self {
+        let module_name = layout.name.clone();
+
+        let base_converter = ModuleMsgConverter::new(module_name.clone(), layout.input_format(), layout.output_format());
+        let command_tracker = Arc::new(CommandTracker::new(&module_name, command_config, &layout.error_states));
+        let operation_runner = Arc::new(OperationRunner::new(&module_name));
+        let command_catalog = Arc::new(CommandCatalog::new(layout));
+
+        Self {
+            name: module_name,
+            base_converter,
+            node,
+            transport,
+            command_tracker,
+            command_catalog,
+            states,
+            interlock,
+            ros_subscriber: Arc::new(Mutex::new(None)),
+            ros_publisher: Arc::new(Mutex::new(None)),
+            command_result_publisher: Arc::new(Mutex::new(None)),
+            command_service: Arc::new(Mutex::new(None)),
+            named_command_service: Arc::new(Mutex::new(None)),
+            operations: layout.operation.clone(),
+            operation_runner,
+            operation_handles: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn command_catalog(&self) -> &CommandCatalog {
+        &self.command_catalog
+    }
+
+    /// build the complete `/set` frame of a command
+    pub fn encode_command(&self, command: u8, value: u16) -> Result<String, EncodeError> {
+        self.base_converter.encode_set_str(command, value)
+    }
+
+    /// single attempt of a command that fails at once while an other command is in flight,
+    /// for callers that block the executor
+    pub fn try_execute_command(&self, command: u8, value: u16) -> Result<O, CommandError> {
+        self.command_catalog.check(command, value)?;
+        self.interlock.check(&self.name, command)?;
+        let payload = self.encode_command(command, value).map_err(CommandError::Encode)?;
+        self.command_tracker.try_execute(self.transport.as_ref(), &payload, self.command_catalog.expected_state(command))
+    }
+
+    fn publish_command_result(&self, command: u8, value: u16, success: bool, message: String) {
+        match self.command_result_publisher.lock() {
+            Ok(publisher_guard) => {
+                if let Some(publisher) = publisher_guard.as_ref() {
+                    if let Err(e) = publisher.publish(CommandResultMsg { command, value, success, message }) {
+                        error!(target: &self.name, "Failed to publish command result: {:?}", e);
+                    }
+                }
+            }
+            Err(_) => error!(target: &self.name, "Failed to acquire lock for command_result_publisher"),
+        }
+    }
+
+}
+
+impl<I: Message, O: Message, S: ServiceType, G: ServiceType> Clone for ModuleConverter<I, O, S, G> {
+    fn clone(&self) -> Self {
+        ModuleConverter {
+            base_converter: self.base_converter.clone(),
+            transport: self.transport.clone(),
+            command_tracker: self.command_tracker.clone(),
+            command_catalog: self.command_catalog.clone(),
+            states: self.states.clone(),
+            interlock: self.interlock.clone(),
+            node: self.node.clone(),
+            name: self.name.clone(),
+            ros_subscriber: Arc::new(Mutex::new(self.ros_subscriber.lock().unwrap().clone())),
+            ros_publisher: Arc::new(Mutex::new(self.ros_publisher.lock().unwrap().clone())),
+            command_result_publisher: Arc::new(Mutex::new(self.command_result_publisher.lock().unwrap().clone())),
+            command_service: Arc::new(Mutex::new(self.command_service.lock().unwrap().clone())),
+            named_command_service: Arc::new(Mutex::new(self.named_command_service.lock().unwrap().clone())),
+            operations: self.operations.clone(),
+            operation_runner: self.operation_runner.clone(),
+            operation_handles: self.operation_handles.clone()
+        }
+    }
+}
+
+impl<I, O, S, G> ModuleConverter<I, O, S, G>
+where
+    I: Message + ModuleInputMsg,
+    O: Message + ModuleOutputMsg,
+    S: ModuleCommandSrv<ModuleOutput = O>,
+    G: ModuleOperationSrv<ModuleOutput = O>,
+{
+    fn start_operations(&self) -> Result<(), Error> {
+        let node = self.node.clone();
+        let mut handles = Vec::new();
+
+        for operation in &self.operations {
+            let topic = format!("{}/{}", self.name, operation.name);
+
+            let feedback_publisher = node.create_publisher::<O>(&format!("{}/feedback", topic), rclrs::QOS_PROFILE_DEFAULT)?;
+            let result_publisher = node.create_publisher::<G::Response>(&format!("{}/result", topic), rclrs::QOS_PROFILE_DEFAULT)?;
+
+            let self_clone = self.clone();
+            let operation_clone = operation.clone();
+            let feedback_clone = feedback_publisher.clone();
+            let result_clone = result_publisher.clone();
+            // the goal is only accepted here, the operation runs on its own thread so
+            // the executor keeps serving the cancel topic
+            let goal_service = node.create_service::<G, _>(
+                &topic,
+                move |_request_id: &rclrs::rmw_request_id_t, request: G::Request| {
+                    let events = match self_clone.operation_runner.begin(&operation_clone.name) {
+                        Ok(events) => events,
+                        Err(e) => return G::response(false, e.to_string(), O::default()),
+                    };
+
+                    let converter = self_clone.clone();
+                    let operation = operation_clone.clone();
+                    let feedback_publisher = feedback_clone.clone();
+                    let result_publisher = result_clone.clone();
+                    let goal = G::goal(&request);
+                    std::thread::spawn(move || {
+                        let result = converter.operation_runner.run(
+                            &operation,
+                            goal,
+                            events,
+                            |command, value| converter.execute_command(command, value),
+                            |output| {
+                                if let Err(e) = feedback_publisher.publish(output.clone()) {
+                                    error!(target: &converter.name, "Failed to publish operation feedback: {:?}", e);
+                                }
+                            },
+                        );
+                        let response = match result {
+                            Ok(output) => G::response(true, String::new(), output),
+                            Err(e) => {
+                                warn!(target: &converter.name, "operation '{}' failed: {}", operation.name, e);
+                                G::response(false, e.to_string(), O::default())
+                            }
+                        };
+                        if let Err(e) = result_publisher.publish(response) {
+                            error!(target: &converter.name, "Failed to publish operation result: {:?}", e);
+                        }
+                    });
+
+                    G::response(true, String::new(), O::default())
+                },
+            )?;
+
+            let runner = self.operation_runner.clone();
+            let name = operation.name.clone();
+            let cancel_subscription = node.create_subscription::<EmptyMsg, _>(
+                &format!("{}/cancel", topic),
+                rclrs::QOS_PROFILE_DEFAULT,
+                move |_msg: EmptyMsg| {
+                    runner.cancel(&name);
+                },
+            )?;
+
+            handles.push(OperationHandles { goal_service, feedback_publisher, result_publisher, cancel_subscription });
+        }
+
+        match self.operation_handles.lock() {
+            Ok(mut handles_guard) => *handles_guard = handles,
+            Err(_) => error!(target: &self.name, "Failed to acquire lock for operation_handles"),
+        }
+        Ok(())
+    }
+}
+
+impl<I, O, S, G> Converter for ModuleConverter<I, O, S, G>
+where
+    I: Message + ModuleInputMsg,
+    O: Message + ModuleOutputMsg,
+    S: ModuleCommandSrv<ModuleOutput = O>,
+    G: ModuleOperationSrv<ModuleOutput = O>,
+{
+    type ModuleInput = I;
+    type ModuleOutput = O;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&self) -> Result<(), Error> {
+        self.base_converter.validate_targets::<Self::ModuleOutput>()?;
+
+        let node = self.node.clone();
+
+        // result of every /input command, successful once the module acknowledged it
+        let result_pub = node.create_publisher::<CommandResultMsg>(
+            &format!("{}/command_result", self.name),
+            rclrs::QOS_PROFILE_DEFAULT
+        )?;
+
+        if let Ok(mut publisher_guard) = self.command_result_publisher.lock() {
+            *publisher_guard = Some(result_pub);
+        } else {
+            error!(target: &self.name, "Failed to acquire lock for command_result_publisher");
+        }
+
+        // waiting for the acknowledgement must not block the executor, one worker sends
+        // the /input commands in the order they arrived
+        let (input_sender, input_receiver) = mpsc::sync_channel::<Self::ModuleInput>(INPUT_QUEUE_CAPACITY);
+        let worker = self.clone();
+        std::thread::spawn(move || {
+            for msg in input_receiver {
+                let (success, message) = match worker.send_command(&msg) {
+                    Ok(_) => (true, String::new()),
+                    Err(e) => {
+                        warn!(target: &worker.name, "command failed: {}", e);
+                        (false, e.to_string())
+                    }
+                };
+                worker.publish_command_result(msg.command(), msg.value(), success, message);
+            }
+        });
+
+        let self_clone = self.clone();
+
+        // ROS to MQTT conversion
+        let ros_sub = node.create_subscription::<Self::ModuleInput, _>(
+            &format!("{}/input", self.name),
+            rclrs::QOS_PROFILE_DEFAULT,
+            move |msg: Self::ModuleInput| {
+                match input_sender.try_send(msg) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(msg)) => {
+                        warn!(target: &self_clone.name, "command queue is full, command {} dropped", msg.command());
+                        let message = format!("{} commands are already queued", INPUT_QUEUE_CAPACITY);
+                        self_clone.publish_command_result(msg.command(), msg.value(), false, message);
+                    }
+                    Err(TrySendError::Disconnected(_)) => error!(target: &self_clone.name, "command worker stopped"),
+                }
+            },
+        )?;
+
+        // command with a synchronous result. rclrs answers a request from its callback only,
+        // so the executor stalls until the module answers. the service makes a single attempt
+        // and refuses to wait behind an other command, a stall lasts at most command.timeout_ms
+        let self_clone = self.clone();
+        let command_srv = node.create_service::<S, _>(
+            &format!("{}/command", self.name),
+            move |_request_id: &rclrs::rmw_request_id_t, request: S::Request| {
+                match self_clone.try_execute_command(S::command(&request), S::value(&request)) {
+                    Ok(output) => S::response(true, String::new(), output),
+                    Err(e) => {
+                        warn!(target: &self_clone.name, "command failed: {}", e);
+                        S::response(false, e.to_string(), O::default())
+                    }
+                }
+            },
+        )?;
+
+        if let Ok(mut service_guard) = self.command_service.lock() {
+            *service_guard = Some(command_srv);
+        } else {
+            error!(target: &self.name, "Failed to acquire lock for command_service");
+        }
+
+        // command addressed by its catalog name instead of its code, stalls the executor
+        // like the command service
+        let self_clone = self.clone();
+        let named_command_srv = node.create_service::<NamedCommand, _>(
+            &format!("{}/named_command", self.name),
+            move |_request_id: &rclrs::rmw_request_id_t, request: NamedCommand_Request| {
+                let result = self_clone.command_catalog
+                    .resolve(&request.command, request.value)
+                    .and_then(|command| self_clone.try_execute_command(command, request.value));
+                match result {
+                    Ok(output) => NamedCommand_Response { success: true, message: String::new(), state: output.state() },
+                    Err(e) => {
+                        warn!(target: &self_clone.name, "command '{}' failed: {}", request.command, e);
+                        NamedCommand_Response { success: false, message: e.to_string(), state: 0 }
+                    }
+                }
+            },
+        )?;
+
+        if let Ok(mut service_guard) = self.named_command_service.lock() {
+            *service_guard = Some(named_command_srv);
+        } else {
+            error!(target: &self.name, "Failed to acquire lock for named_command_service");
+        }
+
+        if let Ok(mut subscriber_guard) = self.ros_subscriber.lock() {
+            *subscriber_guard = Some(ros_sub);
+        } else {
+            error!(target: &self.name, "Failed to acquire lock for ros_publisher");
+        }
+
+        // MQTT to ROS conversion, a late subscriber still receives the last decoded frame
+        let ros_pub = node.create_publisher::<Self::ModuleOutput>(
+            &format!("{}/output", self.name),
+            rclrs::QOS_PROFILE_DEFAULT.keep_last(1).transient_local()
+        )?;
+
+        // Store the publisher
+
+        if let Ok(mut publisher_guard) = self.ros_publisher.lock() {
+            *publisher_guard = Some(ros_pub);
+        } else {
+            error!(target: &self.name, "Failed to acquire lock for ros_publisher");
+        }
+
+        self.start_operations()?;
+
+        if let Err(e) = self.transport.subscribe(&self.name) {
+            error!(target: &self.name, "Failed to subscribe to module frames: {:?}", e);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    fn mqtt_2_ros(&self, mqtt_msg: &str) -> Result<Self::ModuleOutput, DecodeError> {
+        trace!(target: &self.name, "receive /get string: {}", mqtt_msg);
+
+        let module_output = self.base_converter.decode_get_str::<Self::ModuleOutput>(mqtt_msg);
+
+        match module_output {
+            Ok(ref output) => trace!(target: &self.name, "decoded: {:?}", output),
+            Err(ref e) => warn!(target: &self.name, "unable to decode msg: {}", e),
+        }
+
+        module_output
+    }
+
+    fn encode_output(&self, ros_msg: &Self::ModuleOutput) -> Result<String, EncodeError> {
+        let frame = self.base_converter.encode_output(ros_msg);
+
+        match frame {
+            Ok(ref frame) => trace!(target: &self.name, "encoded output: {}", frame),
+            Err(ref e) => warn!(target: &self.name, "unable to encode output msg: {}", e),
+        }
+
+        frame
+    }
+
+    fn send_command(&self, ros_msg: &Self::ModuleInput) -> Result<Self::ModuleOutput, CommandError> {
+        self.command_catalog.check(ros_msg.command(), ros_msg.value())?;
+        self.interlock.check(&self.name, ros_msg.command())?;
+        let payload = self.ros_2_mqtt(ros_msg).map_err(CommandError::Encode)?;
+        let expected = self.command_catalog.expected_state(ros_msg.command());
+        self.command_tracker.execute(self.transport.as_ref(), &payload, expected)
+    }
+
+    fn execute_command(&self, command: u8, value: u16) -> Result<Self::ModuleOutput, CommandError> {
+        self.execute_command_until(command, value, &AtomicBool::new(false))
+    }
+
+    fn execute_command_until(&self, command: u8, value: u16, canceled: &AtomicBool) -> Result<Self::ModuleOutput, CommandError> {
+        self.command_catalog.check(command, value)?;
+        self.interlock.check(&self.name, command)?;
+        let payload = self.encode_command(command, value).map_err(CommandError::Encode)?;
+        let expected = self.command_catalog.expected_state(command);
+        self.command_tracker.execute_until(self.transport.as_ref(), &payload, expected, canceled)
+    }
+
+    fn run_operation(
+        &self,
+        operation: &OperationLayout,
+        goal: u16,
+        canceled: &AtomicBool,
+    ) -> Result<Self::ModuleOutput, OperationError> {
+        let events = self.operation_runner.begin(&operation.name)?;
+        // a cancel from before `begin` found no running operation, once begun `cancel_operation` reaches it
+        if canceled.load(Ordering::SeqCst) {
+            self.operation_runner.finish();
+            return Err(OperationError::Canceled);
+        }
+        self.operation_runner.run(operation, goal, events, |command, value| self.execute_command(command, value), |_| {})
+    }
+
+    fn cancel_operation(&self, operation: &str) -> bool {
+        self.operation_runner.cancel(operation)
+    }
+
+    fn ros_2_mqtt(&self, ros_msg: &Self::ModuleInput) -> Result<String, EncodeError> {
+        debug!(target: &self.name, "receive /input with command: {}, value: {}", ros_msg.command(), ros_msg.value());
+
+        let mqtt_string = self.encode_command(ros_msg.command(), ros_msg.value());
+
+        match mqtt_string {
+            Ok(ref frame) => trace!(target: &self.name, "encoded: {}", frame),
+            Err(ref e) => warn!(target: &self.name, "unable to encode msg: {}", e),
+        }
+
+        mqtt_string
+    }
+
+    fn handle_frame(&self, frame: &str) -> Result<(), DecodeError> {
+        let received = Instant::now();
+        let decoded = self.mqtt_2_ros(frame)
+            .and_then(|ros_msg| Ok((ros_msg, self.base_converter.decode_values(frame)?)));
+        let (ros_msg, values) = match decoded {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                self.states.record_error(&self.name, &e);
+                return Err(e);
+            }
+        };
+        self.states.update(&self.name, values.clone());
+        self.command_tracker.acknowledge(ros_msg.clone(), values.state, received);
+        if self.operation_runner.running().is_some() {
+            self.operation_runner.notify_frame(values, ros_msg.clone(), received);
+        }
+        match self.ros_publisher.lock() {
+            Ok(publisher_guard) => {
+                if let Some(publisher) = publisher_guard.as_ref() {
+                    if let Err(e) = publisher.publish(ros_msg) {
+                        error!(target: &self.name, "Failed to publish ROS message: {:?}", e);
+                    }
+                }
+            }
+            Err(_) => error!(target: &self.name, "Failed to acquire lock for ros_publisher"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use super::{field_value, unknown_field, DecodeError, EncodeError, ModuleInputMsg, ModuleOutputMsg};
+use obd_coffee_maker_interface::msg::{
+    CoffeeFeederInput, CoffeeFeederOutput,
+    CapsuleFeederInput, CapsuleFeederOutput,
+    CupHolderInput, CupHolderOutput,
+    TankInput, TankOutput,
+    PDUInput, PDUOutput,
+    LightInput, LightOutput};
+
+impl ModuleInputMsg for CoffeeFeederInput {
+    fn command(&self) -> u8 {
+        self.command
+    }
+
+    fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+impl ModuleOutputMsg for CoffeeFeederOutput {
+    fn state(&self) -> u8 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u8) {
+        self.state = state;
+    }
+
+    fn set_field(&mut self, target: &str, index: Option<usize>, value: i64) -> Result<(), DecodeError> {
+        match (target, index) {
+            ("capsule", None) => self.capsule = field_value(target, value)?,
+            ("water_level", None) => self.water_level = field_value(target, value)?,
+            ("water_filling", None) => self.water_filling = field_value(target, value)?,
+            ("coffee_feeder", None) => self.coffee_feeder = field_value(target, value)?,
+            _ => return Err(DecodeError::UnknownField { field: target.to_string() }),
+        }
+        Ok(())
+    }
+
+    fn field(&self, target: &str, index: Option<usize>) -> Result<i64, EncodeError> {
+        let value = match (target, index) {
+            ("capsule", None) => self.capsule.into(),
+            ("water_level", None) => self.water_level.into(),
+            ("water_filling", None) => self.water_filling.into(),
+            ("coffee_feeder", None) => self.coffee_feeder.into(),
+            _ => return Err(unknown_field(target)),
+        };
+        Ok(value)
+    }
+}
+
+impl ModuleInputMsg for CapsuleFeederInput {
+    fn command(&self) -> u8 {
+        self.command
+    }
+
+    fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+impl ModuleOutputMsg for CapsuleFeederOutput {
+    fn state(&self) -> u8 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u8) {
+        self.state = state;
+    }
+
+    fn set_field(&mut self, target: &str, index: Option<usize>, value: i64) -> Result<(), DecodeError> {
+        match (target, index) {
+            ("capsule_status_list", Some(i)) => {
+                if self.capsule_status_list.len() <= i {
+                    self.capsule_status_list.resize(i + 1, 0);
+                }
+                self.capsule_status_list[i] = field_value(target, value)?;
+            },
+            ("capsule_detect", None) => self.capsule_detect = value != 0,
+            ("capsule_slot_pos", None) => self.capsule_slot_pos = field_value(target, value)?,
+            ("capsule_selector_pos", None) => self.capsule_selector_pos = field_value(target, value)?,
+            ("home_detect", None) => self.home_detect = field_value(target, value)?,
+            _ => return Err(DecodeError::UnknownField { field: target.to_string() }),
+        }
+        Ok(())
+    }
+
+    fn field(&self, target: &str, index: Option<usize>) -> Result<i64, EncodeError> {
+        let value = match (target, index) {
+            // elements missing from the list are reported as 0
+            ("capsule_status_list", Some(i)) => self.capsule_status_list.get(i).copied().map_or(0, i64::from),
+            ("capsule_detect", None) => self.capsule_detect.into(),
+            ("capsule_slot_pos", None) => self.capsule_slot_pos.into(),
+            ("capsule_selector_pos", None) => self.capsule_selector_pos.into(),
+            ("home_detect", None) => self.home_detect.into(),
+            _ => return Err(unknown_field(target)),
+        };
+        Ok(value)
+    }
+}
+
+impl ModuleInputMsg for CupHolderInput {
+    fn command(&self) -> u8 {
+        self.command
+    }
+
+    fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+impl ModuleOutputMsg for CupHolderOutput {
+    fn state(&self) -> u8 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u8) {
+        self.state = state;
+    }
+
+    fn set_field(&mut self, target: &str, index: Option<usize>, value: i64) -> Result<(), DecodeError> {
+        match (target, index) {
+            ("coffee_detect", None) => self.coffee_detect = value != 0,
+            ("cup_detect", None) => self.cup_detect = value != 0,
+            ("water_detect", None) => self.water_detect = value != 0,
+            ("ice_detect", None) => self.ice_detect = value != 0,
+            // 2 bit field, only 1 reports a running pump
+            ("cup_pump", None) => self.cup_pump = value == 1,
+            ("cup_stock", None) => self.cup_stock = field_value(target, value)?,
+            ("position", None) => self.position = field_value(target, value)?,
+            ("weight", None) => self.weight = field_value(target, value)?,
+            _ => return Err(DecodeError::UnknownField { field: target.to_string() }),
+        }
+        Ok(())
+    }
+
+    fn field(&self, target: &str, index: Option<usize>) -> Result<i64, EncodeError> {
+        let value = match (target, index) {
+            ("coffee_detect", None) => self.coffee_detect.into(),
+            ("cup_detect", None) => self.cup_detect.into(),
+            ("water_detect", None) => self.water_detect.into(),
+            ("ice_detect", None) => self.ice_detect.into(),
+            ("cup_pump", None) => self.cup_pump.into(),
+            ("cup_stock", None) => self.cup_stock.into(),
+            ("position", None) => self.position.into(),
+            ("weight", None) => self.weight.into(),
+            _ => return Err(unknown_field(target)),
+        };
+        Ok(value)
+    }
+}
+
+impl ModuleInputMsg for TankInput {
+    fn command(&self) -> u8 {
+        self.command
+    }
+
+    fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+impl ModuleOutputMsg for TankOutput {
+    fn state(&self) -> u8 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u8) {
+        self.state = state;
+    }
+
+    fn set_field(&mut self, target: &str, index: Option<usize>, value: i64) -> Result<(), DecodeError> {
+        match (target, index) {
+            ("water_quantity", None) => self.water_quantity = field_value(target, value)?,
+            ("waste_quantity", None) => self.waste_quantity = field_value(target, value)?,
+            _ => return Err(DecodeError::UnknownField { field: target.to_string() }),
+        }
+        Ok(())
+    }
+
+    fn field(&self, target: &str, index: Option<usize>) -> Result<i64, EncodeError> {
+        let value = match (target, index) {
+            ("water_quantity", None) => self.water_quantity.into(),
+            ("waste_quantity", None) => self.waste_quantity.into(),
+            _ => return Err(unknown_field(target)),
+        };
+        Ok(value)
+    }
+}
+
+impl ModuleInputMsg for PDUInput {
+    fn command(&self) -> u8 {
+        self.command
+    }
+
+    fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+impl ModuleOutputMsg for PDUOutput {
+    fn state(&self) -> u8 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u8) {
+        self.state = state;
+    }
+
+    fn set_field(&mut self, target: &str, index: Option<usize>, value: i64) -> Result<(), DecodeError> {
+        match (target, index) {
+            ("coffee_pwr", None) => self.coffee_pwr = value != 0,
+            ("capsule_pwr", None) => self.capsule_pwr = value != 0,
+            ("cup_pwr", None) => self.cup_pwr = value != 0,
+            ("tank_pwr", None) => self.tank_pwr = value != 0,
+            ("light_pwr", None) => self.light_pwr = value != 0,
+            ("voltage", None) => self.voltage = field_value(target, value)?,
+            ("current", None) => self.current = field_value(target, value)?,
+            _ => return Err(DecodeError::UnknownField { field: target.to_string() }),
+        }
+        Ok(())
+    }
+
+    fn field(&self, target: &str, index: Option<usize>) -> Result<i64, EncodeError> {
+        let value = match (target, index) {
+            ("coffee_pwr", None) => self.coffee_pwr.into(),
+            ("capsule_pwr", None) => self.capsule_pwr.into(),
+            ("cup_pwr", None) => self.cup_pwr.into(),
+            ("tank_pwr", None) => self.tank_pwr.into(),
+            ("light_pwr", None) => self.light_pwr.into(),
+            ("voltage", None) => self.voltage.into(),
+            ("current", None) => self.current.into(),
+            _ => return Err(unknown_field(target)),
+        };
+        Ok(value)
+    }
+}
+
+impl ModuleInputMsg for LightInput {
+    fn command(&self) -> u8 {
+        self.command
+    }
+
+    fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+impl ModuleOutputMsg for LightOutput {
+    fn state(&self) -> u8 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u8) {
+        self.state = state;
+    }
+
+    fn set_field(&mut self, target: &str, _index: Option<usize>, _value: i64) -> Result<(), DecodeError> {
+        // currently no data contained
+        Err(DecodeError::UnknownField { field: target.to_string() })
+    }
+
+    fn field(&self, target: &str, _index: Option<usize>) -> Result<i64, EncodeError> {
+        Err(unknown_field(target))
+    }
+}
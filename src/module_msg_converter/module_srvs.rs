@@ -0,0 +1,185 @@
+use super::{ModuleCommandSrv, ModuleOperationSrv};
+use obd_coffee_maker_interface::msg::{
+    CoffeeFeederOutput, CapsuleFeederOutput, CupHolderOutput,
+    TankOutput, PDUOutput, LightOutput};
+use obd_coffee_maker_interface::srv::{
+    CoffeeFeederCommand, CoffeeFeederCommand_Request, CoffeeFeederCommand_Response,
+    CapsuleFeederCommand, CapsuleFeederCommand_Request, CapsuleFeederCommand_Response,
+    CupHolderCommand, CupHolderCommand_Request, CupHolderCommand_Response,
+    TankCommand, TankCommand_Request, TankCommand_Response,
+    PDUCommand, PDUCommand_Request, PDUCommand_Response,
+    LightCommand, LightCommand_Request, LightCommand_Response,
+    CoffeeFeederOperation, CoffeeFeederOperation_Request, CoffeeFeederOperation_Response,
+    CapsuleFeederOperation, CapsuleFeederOperation_Request, CapsuleFeederOperation_Response,
+    CupHolderOperation, CupHolderOperation_Request, CupHolderOperation_Response,
+    TankOperation, TankOperation_Request, TankOperation_Response,
+    PDUOperation, PDUOperation_Request, PDUOperation_Response,
+    LightOperation, LightOperation_Request, LightOperation_Response};
+
+impl ModuleCommandSrv for CoffeeFeederCommand {
+    type ModuleOutput = CoffeeFeederOutput;
+
+    fn command(request: &CoffeeFeederCommand_Request) -> u8 {
+        request.command
+    }
+
+    fn value(request: &CoffeeFeederCommand_Request) -> u16 {
+        request.value
+    }
+
+    fn response(success: bool, message: String, output: CoffeeFeederOutput) -> CoffeeFeederCommand_Response {
+        CoffeeFeederCommand_Response { success, message, output }
+    }
+}
+
+impl ModuleCommandSrv for CapsuleFeederCommand {
+    type ModuleOutput = CapsuleFeederOutput;
+
+    fn command(request: &CapsuleFeederCommand_Request) -> u8 {
+        request.command
+    }
+
+    fn value(request: &CapsuleFeederCommand_Request) -> u16 {
+        request.value
+    }
+
+    fn response(success: bool, message: String, output: CapsuleFeederOutput) -> CapsuleFeederCommand_Response {
+        CapsuleFeederCommand_Response { success, message, output }
+    }
+}
+
+impl ModuleCommandSrv for CupHolderCommand {
+    type ModuleOutput = CupHolderOutput;
+
+    fn command(request: &CupHolderCommand_Request) -> u8 {
+        request.command
+    }
+
+    fn value(request: &CupHolderCommand_Request) -> u16 {
+        request.value
+    }
+
+    fn response(success: bool, message: String, output: CupHolderOutput) -> CupHolderCommand_Response {
+        CupHolderCommand_Response { success, message, output }
+    }
+}
+
+impl ModuleCommandSrv for TankCommand {
+    type ModuleOutput = TankOutput;
+
+    fn command(request: &TankCommand_Request) -> u8 {
+        request.command
+    }
+
+    fn value(request: &TankCommand_Request) -> u16 {
+        request.value
+    }
+
+    fn response(success: bool, message: String, output: TankOutput) -> TankCommand_Response {
+        TankCommand_Response { success, message, output }
+    }
+}
+
+impl ModuleCommandSrv for PDUCommand {
+    type ModuleOutput = PDUOutput;
+
+    fn command(request: &PDUCommand_Request) -> u8 {
+        request.command
+    }
+
+    fn value(request: &PDUCommand_Request) -> u16 {
+        request.value
+    }
+
+    fn response(success: bool, message: String, output: PDUOutput) -> PDUCommand_Response {
+        PDUCommand_Response { success, message, output }
+    }
+}
+
+impl ModuleCommandSrv for LightCommand {
+    type ModuleOutput = LightOutput;
+
+    fn command(request: &LightCommand_Request) -> u8 {
+        request.command
+    }
+
+    fn value(request: &LightCommand_Request) -> u16 {
+        request.value
+    }
+
+    fn response(success: bool, message: String, output: LightOutput) -> LightCommand_Response {
+        LightCommand_Response { success, message, output }
+    }
+}
+
+impl ModuleOperationSrv for CoffeeFeederOperation {
+    type ModuleOutput = CoffeeFeederOutput;
+
+    fn goal(request: &CoffeeFeederOperation_Request) -> u16 {
+        request.goal
+    }
+
+    fn response(success: bool, message: String, output: CoffeeFeederOutput) -> CoffeeFeederOperation_Response {
+        CoffeeFeederOperation_Response { success, message, output }
+    }
+}
+
+impl ModuleOperationSrv for CapsuleFeederOperation {
+    type ModuleOutput = CapsuleFeederOutput;
+
+    fn goal(request: &CapsuleFeederOperation_Request) -> u16 {
+        request.goal
+    }
+
+    fn response(success: bool, message: String, output: CapsuleFeederOutput) -> CapsuleFeederOperation_Response {
+        CapsuleFeederOperation_Response { success, message, output }
+    }
+}
+
+impl ModuleOperationSrv for CupHolderOperation {
+    type ModuleOutput = CupHolderOutput;
+
+    fn goal(request: &CupHolderOperation_Request) -> u16 {
+        request.goal
+    }
+
+    fn response(success: bool, message: String, output: CupHolderOutput) -> CupHolderOperation_Response {
+        CupHolderOperation_Response { success, message, output }
+    }
+}
+
+impl ModuleOperationSrv for TankOperation {
+    type ModuleOutput = TankOutput;
+
+    fn goal(request: &TankOperation_Request) -> u16 {
+        request.goal
+    }
+
+    fn response(success: bool, message: String, output: TankOutput) -> TankOperation_Response {
+        TankOperation_Response { success, message, output }
+    }
+}
+
+impl ModuleOperationSrv for PDUOperation {
+    type ModuleOutput = PDUOutput;
+
+    fn goal(request: &PDUOperation_Request) -> u16 {
+        request.goal
+    }
+
+    fn response(success: bool, message: String, output: PDUOutput) -> PDUOperation_Response {
+        PDUOperation_Response { success, message, output }
+    }
+}
+
+impl ModuleOperationSrv for LightOperation {
+    type ModuleOutput = LightOutput;
+
+    fn goal(request: &LightOperation_Request) -> u16 {
+        request.goal
+    }
+
+    fn response(success: bool, message: String, output: LightOutput) -> LightOperation_Response {
+        LightOperation_Response { success, message, output }
+    }
+}
@@ -0,0 +1,274 @@
+//! long running commands of a module. rclrs offers no action server, so an operation is
+//! exposed as an action made by hand: a goal service that only accepts or refuses the
+//! goal, a `feedback` topic with every decoded frame while it runs, a `result` topic
+//! published once it ended and a `cancel` topic
+//!
+//! ```text
+//! <namespace>/<operation>             goal service
+//! <namespace>/<operation>/feedback
+//! <namespace>/<operation>/result
+//! <namespace>/<operation>/cancel
+//! ```
+//!
+//! the recipe server exposes recipes the same way below `recipe/`
+
+use crate::command_tracker::CommandError;
+use crate::module_layout::OperationLayout;
+use crate::module_msg_converter::DecodedValues;
+use std::{fmt, sync::{mpsc, Mutex}, time::{Duration, Instant}};
+
+/// reason an operation did not reach its goal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationError {
+    /// another operation of the module is running
+    Busy { running: String },
+    /// the command of the operation was not acknowledged
+    Command(CommandError),
+    /// canceled before the goal was reached
+    Canceled,
+    /// goal not reached within the timeout of the operation
+    Timeout { timeout_ms: u64 },
+}
+
+impl OperationError {
+    /// short name of the failure class, stable for counting and logging
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OperationError::Busy { .. } => "busy",
+            OperationError::Command(e) => e.kind(),
+            OperationError::Canceled => "canceled",
+            OperationError::Timeout { .. } => "timeout",
+        }
+    }
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationError::Busy { running } => write!(f, "operation '{}' is still running", running),
+            OperationError::Command(e) => write!(f, "{}", e),
+            OperationError::Canceled => write!(f, "operation canceled"),
+            OperationError::Timeout { timeout_ms } => write!(f, "goal not reached within {} ms", timeout_ms),
+        }
+    }
+}
+
+impl std::error::Error for OperationError {}
+
+/// input of a running operation
+pub enum OperationEvent<O> {
+    Frame(DecodedValues, O),
+    Cancel,
+}
+
+struct ActiveOperation<O> {
+    name: String,
+    events: mpsc::Sender<OperationEvent<O>>,
+    /// frames received earlier report the state before the goal was accepted
+    began: Instant,
+}
+
+/// runs the operations of one module, a module executes one operation at a time
+pub struct OperationRunner<O> {
+    namespace: String,
+    active: Mutex<Option<ActiveOperation<O>>>,
+}
+
+impl<O> OperationRunner<O> {
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            active: Mutex::new(None),
+        }
+    }
+
+    /// name of the running operation
+    pub fn running(&self) -> Option<String> {
+        self.active
+            .lock()
+            .ok()
+            .and_then(|active| active.as_ref().map(|active| active.name.clone()))
+    }
+
+    /// reserve the module for `operation`, the returned receiver is passed to `run`
+    pub fn begin(&self, operation: &str) -> Result<mpsc::Receiver<OperationEvent<O>>, OperationError> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(running) = active.as_ref() {
+            return Err(OperationError::Busy { running: running.name.clone() });
+        }
+        let (events, receiver) = mpsc::channel();
+        *active = Some(ActiveOperation { name: operation.to_string(), events, began: Instant::now() });
+        Ok(receiver)
+    }
+
+    /// hand a decoded `/get` frame, received at `received`, to the running operation
+    /// unless it was received before the operation began
+    pub fn notify_frame(&self, values: DecodedValues, output: O, received: Instant) {
+        if let Ok(active) = self.active.lock() {
+            if let Some(active) = active.as_ref().filter(|active| received >= active.began) {
+                let _ = active.events.send(OperationEvent::Frame(values, output));
+            }
+        }
+    }
+
+    /// cancel `operation` if it is running, returns whether it was
+    pub fn cancel(&self, operation: &str) -> bool {
+        match self.active.lock() {
+            Ok(active) => match active.as_ref() {
+                Some(active) if active.name == operation => active.events.send(OperationEvent::Cancel).is_ok(),
+                _ => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// send the command of `operation` with `goal` as value and wait until a decoded frame
+    /// meets the completion predicate, the operation is canceled or times out.
+    /// canceled and timed out operations are stopped with the stop command, if any
+    pub fn run<E, F>(
+        &self,
+        operation: &OperationLayout,
+        goal: u16,
+        events: mpsc::Receiver<OperationEvent<O>>,
+        execute: E,
+        feedback: F,
+    ) -> Result<O, OperationError>
+    where
+        E: Fn(u8, u16) -> Result<O, CommandError>,
+        F: Fn(&O),
+    {
+        let result = self.wait_for_goal(operation, goal, &events, &execute, &feedback);
+        if let (Err(OperationError::Canceled | OperationError::Timeout { .. }), Some(stop_command)) = (&result, operation.stop_command) {
+            if let Err(e) = execute(stop_command, operation.stop_value) {
+                log::error!(target: &self.namespace, "cannot stop operation '{}': {}", operation.name, e);
+            }
+        }
+        self.finish();
+        result
+    }
+
+    /// release the module reserved by `begin` without running the operation
+    pub fn finish(&self) {
+        if let Ok(mut active) = self.active.lock() {
+            *active = None;
+        }
+    }
+
+    fn wait_for_goal<E, F>(
+        &self,
+        operation: &OperationLayout,
+        goal: u16,
+        events: &mpsc::Receiver<OperationEvent<O>>,
+        execute: &E,
+        feedback: &F,
+    ) -> Result<O, OperationError>
+    where
+        E: Fn(u8, u16) -> Result<O, CommandError>,
+        F: Fn(&O),
+    {
+        let timeout = Duration::from_millis(operation.timeout_ms);
+        let deadline = Instant::now() + timeout;
+
+        // frames received while waiting for the acknowledgement stay queued in `events`
+        execute(operation.command, goal).map_err(OperationError::Command)?;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match events.recv_timeout(remaining) {
+                Ok(OperationEvent::Frame(values, output)) => {
+                    feedback(&output);
+                    let done = values
+                        .get(&operation.done.field)
+                        .is_some_and(|current| operation.done.is_met(current, goal as i64));
+                    if done {
+                        return Ok(output);
+                    }
+                }
+                Ok(OperationEvent::Cancel) => return Err(OperationError::Canceled),
+                Err(_) => return Err(OperationError::Timeout { timeout_ms: operation.timeout_ms }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    fn operation() -> OperationLayout {
+        toml::from_str(
+            r#"
+            name = "fill_water"
+            command = 1
+            stop_command = 0
+            timeout_ms = 2000
+            done = { field = "water_level", compare = "at_least" }
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn frame(water_level: i64) -> (DecodedValues, i64) {
+        (DecodedValues { state: 1, fields: vec![("water_level".to_string(), water_level)] }, water_level)
+    }
+
+    #[test]
+    fn completes_once_the_goal_is_met() {
+        let runner = Arc::new(OperationRunner::new("coffee_feeder"));
+        let events = runner.begin("fill_water").unwrap();
+        assert_eq!(runner.begin("fill_water").err(), Some(OperationError::Busy { running: "fill_water".to_string() }));
+
+        let runner_clone = runner.clone();
+        let execute = move |command: u8, value: u16| {
+            assert_eq!((command, value), (1, 3));
+            for level in 2..=3 {
+                let (values, output) = frame(level);
+                runner_clone.notify_frame(values, output, Instant::now());
+            }
+            Ok(0)
+        };
+        assert_eq!(runner.run(&operation(), 3, events, execute, |_| {}), Ok(3));
+        assert_eq!(runner.running(), None);
+    }
+
+    #[test]
+    fn ignores_frames_received_before_begin() {
+        let runner = Arc::new(OperationRunner::new("coffee_feeder"));
+        let before = Instant::now();
+        let events = runner.begin("fill_water").unwrap();
+
+        let runner_clone = runner.clone();
+        let execute = move |command: u8, _| {
+            if command == 1 {
+                let (values, output) = frame(3);
+                runner_clone.notify_frame(values, output, before);
+                runner_clone.cancel("fill_water");
+            }
+            Ok(0)
+        };
+        assert_eq!(runner.run(&operation(), 3, events, execute, |_| {}), Err(OperationError::Canceled));
+    }
+
+    #[test]
+    fn cancel_sends_the_stop_command() {
+        let runner = Arc::new(OperationRunner::new("coffee_feeder"));
+        let events = runner.begin("fill_water").unwrap();
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let runner_clone = runner.clone();
+        let canceler = thread::spawn(move || {
+            while !runner_clone.cancel("fill_water") {
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        let sent_clone = sent.clone();
+        let execute = move |command: u8, value: u16| {
+            sent_clone.lock().unwrap().push((command, value));
+            Ok(0)
+        };
+        assert_eq!(runner.run(&operation(), 3, events, execute, |_| {}), Err(OperationError::Canceled));
+        canceler.join().unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![(1, 3), (0, 0)]);
+    }
+}
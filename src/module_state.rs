@@ -0,0 +1,203 @@
+use crate::module_msg_converter::{DecodeError, DecodedValues};
+use std::{collections::{BTreeMap, HashMap}, sync::Mutex, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use builtin_interfaces::msg::Time;
+
+pub mod module_state_server;
+
+pub use module_state_server::ModuleStateServer;
+
+/// latest decoded `/get` frame of a module and when it was received
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleSnapshot {
+    pub values: DecodedValues,
+    /// wall clock time of the frame, for stamps
+    pub received: SystemTime,
+    /// monotonic time of the frame, for ages that survive clock adjustments
+    pub received_at: Instant,
+}
+
+impl ModuleSnapshot {
+    /// time since the frame was received
+    pub fn age(&self) -> Duration {
+        self.received_at.elapsed()
+    }
+}
+
+/// frames of a module since the driver started
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStats {
+    /// frames decoded
+    pub decoded: u64,
+    /// frames rejected, by `DecodeError::kind`
+    pub errors: BTreeMap<&'static str, u64>,
+}
+
+/// latest decoded state of every module, updated by the converters
+#[derive(Debug, Default)]
+pub struct ModuleStates {
+    states: Mutex<HashMap<String, ModuleSnapshot>>,
+    stats: Mutex<HashMap<String, FrameStats>>,
+}
+
+impl ModuleStates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// store a frame of `module` received now
+    pub fn update(&self, module: &str, values: DecodedValues) {
+        let snapshot = ModuleSnapshot { values, received: SystemTime::now(), received_at: Instant::now() };
+        match self.states.lock() {
+            Ok(mut states) => {
+                states.insert(module.to_string(), snapshot);
+            }
+            Err(_) => log::error!("Failed to acquire lock for states"),
+        }
+        self.update_stats(module, |stats| stats.decoded += 1);
+    }
+
+    /// count a frame of `module` that could not be decoded
+    pub fn record_error(&self, module: &str, error: &DecodeError) {
+        self.update_stats(module, |stats| *stats.errors.entry(error.kind()).or_default() += 1);
+    }
+
+    pub fn stats(&self, module: &str) -> FrameStats {
+        self.stats
+            .lock()
+            .ok()
+            .and_then(|stats| stats.get(module).cloned())
+            .unwrap_or_default()
+    }
+
+    fn update_stats<F: FnOnce(&mut FrameStats)>(&self, module: &str, update: F) {
+        match self.stats.lock() {
+            Ok(mut stats) => update(stats.entry(module.to_string()).or_default()),
+            Err(_) => log::error!("Failed to acquire lock for stats"),
+        }
+    }
+
+    /// latest frame of `module`, if one arrived
+    pub fn get(&self, module: &str) -> Option<ModuleSnapshot> {
+        self.states.lock().ok().and_then(|states| states.get(module).cloned())
+    }
+
+    /// time since the latest frame of `module`
+    pub fn age(&self, module: &str) -> Option<Duration> {
+        self.get(module).map(|snapshot| snapshot.age())
+    }
+
+    /// latest frame of every module that sent one, sorted by module name
+    pub fn snapshot(&self) -> Vec<(String, ModuleSnapshot)> {
+        let mut snapshot: Vec<_> = match self.states.lock() {
+            Ok(states) => states.iter().map(|(module, state)| (module.clone(), state.clone())).collect(),
+            Err(_) => Vec::new(),
+        };
+        snapshot.sort_by(|(a, _), (b, _)| a.cmp(b));
+        snapshot
+    }
+}
+
+impl FrameStats {
+    pub fn rejected(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    /// share of all received frames rejected with `kind`
+    pub fn error_rate(&self, kind: &str) -> f64 {
+        let received = self.decoded + self.rejected();
+        match received {
+            0 => 0.0,
+            received => self.errors.get(kind).copied().unwrap_or(0) as f64 / received as f64,
+        }
+    }
+}
+
+/// ROS time of `time`
+pub fn time_msg(time: SystemTime) -> Time {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Time { sec: since_epoch.as_secs() as i32, nanosec: since_epoch.subsec_nanos() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn values(state: u8, water_quantity: i64) -> DecodedValues {
+        DecodedValues { state, fields: vec![("water_quantity".to_string(), water_quantity)] }
+    }
+
+    fn checksum_error() -> DecodeError {
+        DecodeError::ChecksumMismatch { received: "00".to_string(), calculated: "4F".to_string() }
+    }
+
+    #[test]
+    fn keeps_the_latest_frame_of_every_module() {
+        let states = ModuleStates::new();
+        assert_eq!(states.get("Tank"), None);
+        assert_eq!(states.age("Tank"), None);
+
+        states.update("Tank", values(0, 1500));
+        states.update("Tank", values(1, 1400));
+        let snapshot = states.get("Tank").unwrap();
+        assert_eq!(snapshot.values, values(1, 1400));
+        assert_eq!(snapshot.values.get("water_quantity"), Some(1400));
+        assert_eq!(states.get("light"), None);
+    }
+
+    #[test]
+    fn counts_decoded_and_rejected_frames() {
+        let states = ModuleStates::new();
+        assert_eq!(states.stats("Tank"), FrameStats::default());
+        assert_eq!(states.stats("Tank").error_rate("checksum_mismatch"), 0.0);
+
+        states.update("Tank", values(0, 1500));
+        states.update("Tank", values(0, 1500));
+        states.update("Tank", values(0, 1500));
+        states.record_error("Tank", &checksum_error());
+        let length = DecodeError::LengthMismatch { expected: 19, actual: 18 };
+        states.record_error("light", &length);
+
+        let stats = states.stats("Tank");
+        assert_eq!((stats.decoded, stats.rejected()), (3, 1));
+        assert_eq!(stats.errors.get("checksum_mismatch"), Some(&1));
+        assert_eq!(stats.error_rate("checksum_mismatch"), 0.25);
+        assert_eq!(stats.error_rate("length_mismatch"), 0.0);
+        // a rejected frame does not count as state of the module
+        assert_eq!(states.stats("light").rejected(), 1);
+        assert_eq!(states.get("light"), None);
+    }
+
+    #[test]
+    fn age_grows_until_the_next_frame() {
+        let states = ModuleStates::new();
+        states.update("Tank", values(0, 1500));
+        thread::sleep(Duration::from_millis(20));
+        let age = states.age("Tank").unwrap();
+        assert!(age >= Duration::from_millis(20), "{:?}", age);
+
+        states.update("Tank", values(0, 1500));
+        assert!(states.age("Tank").unwrap() < age);
+    }
+
+    #[test]
+    fn snapshot_lists_the_modules_by_name() {
+        let states = ModuleStates::new();
+        states.update("light", values(1, 0));
+        states.update("Tank", values(0, 1500));
+        states.update("cup_holder", values(0, 0));
+        states.record_error("pdu", &checksum_error());
+
+        let snapshot = states.snapshot();
+        let modules: Vec<&str> = snapshot.iter().map(|(module, _)| module.as_str()).collect();
+        assert_eq!(modules, vec!["Tank", "cup_holder", "light"]);
+        assert_eq!(snapshot[2].1.values, values(1, 0));
+    }
+
+    #[test]
+    fn time_msg_splits_seconds_and_nanoseconds() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 250_000_000);
+        let msg = time_msg(time);
+        assert_eq!((msg.sec, msg.nanosec), (1_700_000_000, 250_000_000));
+    }
+}
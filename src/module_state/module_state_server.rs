@@ -0,0 +1,40 @@
+use super::{time_msg, ModuleSnapshot, ModuleStates};
+use std::sync::Arc;
+use anyhow::{Error, Result};
+use rclrs::{Node, Service};
+use obd_coffee_maker_interface::msg::ModuleState as ModuleStateMsg;
+use obd_coffee_maker_interface::srv::{GetModuleStates, GetModuleStates_Request, GetModuleStates_Response};
+
+/// ROS side of the module state store
+pub struct ModuleStateServer {
+    /// `module_states`, the latest decoded frame of every module that sent one
+    pub snapshot_service: Arc<Service<GetModuleStates>>,
+}
+
+impl ModuleStateServer {
+    pub fn start(node: &Arc<Node>, states: Arc<ModuleStates>) -> Result<Self, Error> {
+        let snapshot_service = node.create_service::<GetModuleStates, _>(
+            "module_states",
+            move |_request_id: &rclrs::rmw_request_id_t, _request: GetModuleStates_Request| {
+                let modules = states
+                    .snapshot()
+                    .into_iter()
+                    .map(|(module, snapshot)| to_msg(module, snapshot))
+                    .collect();
+                GetModuleStates_Response { modules }
+            },
+        )?;
+        Ok(Self { snapshot_service })
+    }
+}
+
+fn to_msg(module: String, snapshot: ModuleSnapshot) -> ModuleStateMsg {
+    let (fields, values) = snapshot.values.fields.into_iter().unzip();
+    ModuleStateMsg {
+        module,
+        stamp: time_msg(snapshot.received),
+        state: snapshot.values.state,
+        fields,
+        values,
+    }
+}
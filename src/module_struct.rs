@@ -1,11 +1,33 @@
 ///////////////////////////////////////////////////////////
-///                  Struct Defination                 ////
+//                   Struct Defination                 ////
 ///////////////////////////////////////////////////////////
 
+pub struct BitField {
+    pub name: String,
+    pub offset: u32,
+    pub width: u32,
+    pub signed: bool,
+    pub target: String
+}
+
+impl Clone for BitField{
+    fn clone(&self) -> Self {
+        BitField {
+            name : self.name.clone(),
+            offset : self.offset,
+            width : self.width,
+            signed : self.signed,
+            target : self.target.clone(),
+        }
+    }
+}
+
 pub struct ModuleDataField {
     pub size: usize,
     pub index: usize,
-    pub string: String
+    pub string: String,
+    pub signed: bool,
+    pub bit_fields: Vec<BitField>
 }
 
 impl Clone for ModuleDataField{
@@ -14,6 +36,8 @@ impl Clone for ModuleDataField{
             size : self.size,
             index : self.index,
             string : self.string.clone(),
+            signed : self.signed,
+            bit_fields : self.bit_fields.clone(),
         }
     }
 }
@@ -88,15 +112,80 @@ impl Clone for ModuleOutputFormat{
 }
 
 ///////////////////////////////////////////////////////////
-///                  Struct Implementation             ////
+//                   Struct Implementation             ////
 ///////////////////////////////////////////////////////////
+impl BitField {
+    pub fn new(
+        name: String,
+        offset: u32,
+        width: u32,
+        signed: bool,
+        target: String,
+    ) -> Self {
+        Self {name, offset, width, signed, target}
+    }
+
+    /// extract this bit field from the decoded integer value of its payload field
+    pub fn extract(&self, raw: u32) -> i64 {
+        // the layout keeps width in 1..=32, checked shifts keep a hand built field from panicking
+        let mask = 1u64.checked_shl(self.width).map_or(u64::MAX, |bit| bit - 1);
+        let value = (raw as u64).checked_shr(self.offset).unwrap_or(0) & mask;
+        if self.signed && (1..=32).contains(&self.width) && (value >> (self.width - 1)) & 1 == 1 {
+            value as i64 - (1i64 << self.width)
+        } else {
+            value as i64
+        }
+    }
+
+    /// inverse of `extract`, the bits of `value` moved to this bit field, `None` when
+    /// `value` is out of the range of `width` bits
+    pub fn insert(&self, value: i64) -> Option<u64> {
+        if !(1..=32).contains(&self.width) {
+            return None;
+        }
+        let (min, max) = if self.signed {
+            (-(1i64 << (self.width - 1)), (1i64 << (self.width - 1)) - 1)
+        } else {
+            (0, (1i64 << self.width) - 1)
+        };
+        if value < min || value > max {
+            return None;
+        }
+        let mask = (1u64 << self.width) - 1;
+        ((value as u64) & mask).checked_shl(self.offset)
+    }
+}
+
 impl ModuleDataField {
     pub fn new(
         size: usize,
         index: usize,
         string: String,
     ) -> Self {
-        Self {size, index, string}
+        Self {size, index, string, signed: false, bit_fields: Vec::new()}
+    }
+
+    /// payload field holding a decimal number whose bits are split into `bit_fields`,
+    /// a `signed` field carries its sign as the first character ('-' or '0')
+    pub fn new_payload(
+        size: usize,
+        index: usize,
+        string: String,
+        signed: bool,
+        bit_fields: Vec<BitField>,
+    ) -> Self {
+        Self {size, index, string, signed, bit_fields}
+    }
+
+    /// number of digits holding the magnitude of the field
+    pub fn digits(&self) -> usize {
+        if self.signed { self.size - 1 } else { self.size }
+    }
+
+    /// number of bits addressable by the largest decimal value that fits the field
+    pub fn bit_capacity(&self) -> u32 {
+        let max_value = 10u64.saturating_pow(self.digits() as u32) - 1;
+        u64::BITS - max_value.leading_zeros()
     }
 }
 
@@ -0,0 +1,207 @@
+use crate::module_layout::ModuleLayout;
+use crate::module_msg_converter::ModuleMsgConverter;
+use crate::module_state::ModuleStates;
+use crate::transport::FrameTransport;
+use std::{sync::Arc, thread, time::{Duration, Instant}};
+use anyhow::{anyhow, Error, Result};
+use log::{error, info, warn};
+use rclrs::{Node, Publisher};
+use std_msgs::msg::Bool as BoolMsg;
+
+/// flags a module offline when no `/get` frame arrived within its watchdog timeout
+/// and pokes it with the poll command until it answers again
+pub struct ModuleWatchdog {
+    namespace: String,
+    timeout: Duration,
+    /// `/set` frame of the poll command
+    poll_frame: Option<String>,
+    states: Arc<ModuleStates>,
+    transport: Arc<dyn FrameTransport>,
+    /// `<namespace>/online`, latched so late subscribers get the current status
+    pub online_publisher: Arc<Publisher<BoolMsg>>,
+}
+
+impl ModuleWatchdog {
+    /// start supervising `layout`, nothing is started when it has no watchdog
+    pub fn start(
+        node: &Arc<Node>,
+        layout: &ModuleLayout,
+        states: Arc<ModuleStates>,
+        transport: Arc<dyn FrameTransport>,
+    ) -> Result<Option<Arc<Self>>, Error> {
+        let watchdog = match Self::new(node, layout, states, transport)? {
+            Some(watchdog) => Arc::new(watchdog),
+            None => return Ok(None),
+        };
+
+        let watchdog_clone = watchdog.clone();
+        thread::Builder::new()
+            .name(format!("{}-watchdog", layout.name))
+            .spawn(move || watchdog_clone.run())
+            .map_err(|e| anyhow!("[{}] cannot start watchdog: {}", layout.name, e))?;
+        Ok(Some(watchdog))
+    }
+
+    fn new(
+        node: &Arc<Node>,
+        layout: &ModuleLayout,
+        states: Arc<ModuleStates>,
+        transport: Arc<dyn FrameTransport>,
+    ) -> Result<Option<Self>, Error> {
+        let watchdog = match &layout.watchdog {
+            Some(watchdog) => watchdog,
+            None => return Ok(None),
+        };
+        let online_publisher = node.create_publisher::<BoolMsg>(
+            &format!("{}/online", layout.name),
+            rclrs::QOS_PROFILE_DEFAULT.keep_last(1).transient_local(),
+        )?;
+        let converter = ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format());
+        let poll_frame = watchdog
+            .poll(layout)?
+            .map(|(command, value)| converter.encode_set_str(command, value))
+            .transpose()
+            .map_err(|e| anyhow!("[{}] cannot encode the poll command: {}", layout.name, e))?;
+        Ok(Some(Self {
+            namespace: layout.name.clone(),
+            timeout: watchdog.timeout(),
+            poll_frame,
+            states,
+            transport,
+            online_publisher,
+        }))
+    }
+
+    fn run(&self) {
+        // a module that never answered counts from the start of the driver
+        let started = Instant::now();
+        let mut online = None;
+        loop {
+            let (changed, next_check) = self.check(started, &mut online);
+            if let Some(is_online) = changed {
+                if let Err(e) = self.online_publisher.publish(BoolMsg { data: is_online }) {
+                    error!(target: &self.namespace, "Failed to publish online state: {:?}", e);
+                }
+            }
+            thread::sleep(next_check);
+        }
+    }
+
+    /// update `online` from the age of the latest frame and poll the module while it is
+    /// offline. returns the new state when it changed and the wait until the next check
+    fn check(&self, started: Instant, online: &mut Option<bool>) -> (Option<bool>, Duration) {
+        let (age, answered) = match self.states.age(&self.namespace) {
+            Some(age) => (age, true),
+            None => (started.elapsed(), false),
+        };
+        let is_online = age < self.timeout;
+        let mut changed = None;
+        // nothing is published before the first frame or the end of the first timeout
+        if *online != Some(is_online) && (answered || !is_online) {
+            if is_online {
+                info!(target: &self.namespace, "module is online");
+            } else {
+                warn!(target: &self.namespace, "no /get frame for {} ms, module is offline", age.as_millis());
+            }
+            *online = Some(is_online);
+            changed = Some(is_online);
+        }
+
+        // the poll is not tracked as a command, its answer ends the offline state
+        if let (false, Some(poll_frame)) = (is_online, &self.poll_frame) {
+            if let Err(e) = self.transport.send_frame(&self.namespace, poll_frame) {
+                warn!(target: &self.namespace, "cannot send poll command: {:#}", e);
+            }
+        }
+        // wake up when the latest frame would expire, at least every quarter timeout
+        (changed, self.timeout.saturating_sub(age).max(self.timeout / 4))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_catalog::CommandRef;
+    use crate::module_layout::{ModuleLayouts, WatchdogLayout};
+    use crate::module_msg_converter::DecodedValues;
+    use crate::transport::InMemoryTransport;
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    /// watchdog of the light polling with its status command, nothing runs in the background
+    fn watchdog(transport: Arc<InMemoryTransport>) -> (ModuleWatchdog, Arc<ModuleStates>) {
+        let layouts = ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap();
+        let mut layout = layouts.get("light").unwrap().clone();
+        layout.watchdog = Some(WatchdogLayout {
+            timeout_ms: TIMEOUT.as_millis() as u64,
+            poll_command: Some(CommandRef::Name("status".to_string())),
+            poll_value: 0,
+        });
+        let context = rclrs::Context::new([]).unwrap();
+        let node = rclrs::create_node(&context, "module_watchdog_test").unwrap();
+        let states = Arc::new(ModuleStates::new());
+        let watchdog = ModuleWatchdog::new(&node, &layout, states.clone(), transport).unwrap().unwrap();
+        (watchdog, states)
+    }
+
+    fn frame_received(states: &ModuleStates) {
+        states.update("light", DecodedValues { state: 0, fields: Vec::new() });
+    }
+
+    #[test]
+    fn reports_every_transition_once() {
+        let transport = Arc::new(InMemoryTransport::new());
+        let (watchdog, states) = watchdog(transport.clone());
+        let started = Instant::now();
+        let mut online = None;
+
+        // silent until the first frame or the first timeout
+        assert_eq!(watchdog.check(started, &mut online).0, None);
+        frame_received(&states);
+        assert_eq!(watchdog.check(started, &mut online).0, Some(true));
+        assert_eq!(watchdog.check(started, &mut online).0, None);
+
+        thread::sleep(TIMEOUT);
+        assert_eq!(watchdog.check(started, &mut online).0, Some(false));
+        assert_eq!(watchdog.check(started, &mut online).0, None);
+
+        frame_received(&states);
+        assert_eq!(watchdog.check(started, &mut online).0, Some(true));
+        assert_eq!(online, Some(true));
+    }
+
+    #[test]
+    fn module_that_never_answered_goes_offline_after_the_timeout() {
+        let transport = Arc::new(InMemoryTransport::new());
+        let (watchdog, _states) = watchdog(transport);
+        let mut online = None;
+        let started = Instant::now() - TIMEOUT;
+        assert_eq!(watchdog.check(started, &mut online).0, Some(false));
+    }
+
+    #[test]
+    fn polls_an_offline_module_without_waiting_for_an_answer() {
+        let transport = Arc::new(InMemoryTransport::new());
+        let (watchdog, states) = watchdog(transport.clone());
+        let started = Instant::now() - TIMEOUT;
+        let mut online = None;
+
+        let checked = Instant::now();
+        let (_, next_check) = watchdog.check(started, &mut online);
+        watchdog.check(started, &mut online);
+        // nothing answers the poll, the check still returns at once
+        assert!(checked.elapsed() < TIMEOUT, "{:?}", checked.elapsed());
+        assert_eq!(next_check, TIMEOUT / 4);
+
+        let layouts = ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap();
+        let layout = layouts.get("light").unwrap();
+        let converter = ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format());
+        let poll = ("light".to_string(), converter.encode_set_str(9, 0).unwrap());
+        assert_eq!(transport.take_sent_frames(), vec![poll.clone(), poll]);
+
+        // an online module is not polled
+        frame_received(&states);
+        watchdog.check(started, &mut online);
+        assert!(transport.sent_frames().is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde::Deserialize;
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::command_catalog::{CommandCatalog, CommandRef};
+use crate::module_layout::{CompletionLayout, ModuleLayouts, OperationLayout};
+
+pub mod recipe_executor;
+pub mod recipe_server;
+
+pub use recipe_executor::{RecipeError, RecipeExecutor};
+pub use recipe_server::RecipeServer;
+
+///////////////////////////////////////////////////////////
+//                   Struct Defination                 ////
+///////////////////////////////////////////////////////////
+
+/// content of a recipe file, one `[[recipe]]` table per recipe
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipes {
+    #[serde(default)]
+    pub recipe: Vec<Recipe>,
+}
+
+/// ordered steps executed one after another, the first failing step ends the recipe
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipe {
+    pub name: String,
+    pub step: Vec<RecipeStep>,
+}
+
+/// one step, either a raw `command` of a module or one of its `operation`s
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecipeStep {
+    /// namespace of the module
+    pub module: String,
+    #[serde(default)]
+    pub command: Option<CommandRef>,
+    #[serde(default)]
+    pub operation: Option<String>,
+    /// command value, or the goal of an operation
+    #[serde(default)]
+    pub value: u16,
+    /// decoded state the module must reach after `command`, compared to `value` by default
+    #[serde(default)]
+    pub wait: Option<CompletionLayout>,
+    /// time allowed for `wait`
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// command sent when `wait` times out or the recipe is canceled
+    #[serde(default)]
+    pub stop_command: Option<u8>,
+}
+
+/// what a step does, resolved against the layout of its module
+#[derive(Debug, Clone)]
+pub enum StepAction {
+    Command { command: u8, value: u16 },
+    Operation { operation: OperationLayout, goal: u16 },
+}
+
+///////////////////////////////////////////////////////////
+//                   Struct Implementation             ////
+///////////////////////////////////////////////////////////
+
+impl Recipes {
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               construction                                             ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn from_toml(content: &str, module_layouts: &ModuleLayouts) -> Result<Self, Error> {
+        let recipes: Recipes = toml::from_str(content)?;
+        recipes.validate(module_layouts)?;
+        Ok(recipes)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P, module_layouts: &ModuleLayouts) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("cannot read recipe file '{}'", path.display()))?;
+        Self::from_toml(&content, module_layouts)
+            .with_context(|| format!("invalid recipe file '{}'", path.display()))
+    }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    ////               class functions                                          ////
+    ////////////////////////////////////////////////////////////////////////////////
+    pub fn get(&self, name: &str) -> Result<&Recipe, Error> {
+        self.recipe
+            .iter()
+            .find(|recipe| recipe.name == name)
+            .ok_or_else(|| anyhow!("recipe '{}' is not defined", name))
+    }
+
+    pub fn validate(&self, module_layouts: &ModuleLayouts) -> Result<(), Error> {
+        let mut names = HashSet::new();
+        for recipe in &self.recipe {
+            if !names.insert(recipe.name.as_str()) {
+                bail!("recipe '{}' is defined more than once", recipe.name);
+            }
+            recipe
+                .validate(module_layouts)
+                .with_context(|| format!("[{}] invalid recipe", recipe.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl Recipe {
+    pub fn validate(&self, module_layouts: &ModuleLayouts) -> Result<(), Error> {
+        if self.step.is_empty() {
+            bail!("recipe has no step");
+        }
+        for (i, step) in self.step.iter().enumerate() {
+            step.action(module_layouts)
+                .with_context(|| format!("step {} ({})", i + 1, step.module))?;
+        }
+        Ok(())
+    }
+}
+
+impl RecipeStep {
+    /// resolve the step against the layout of its module
+    pub fn action(&self, module_layouts: &ModuleLayouts) -> Result<StepAction, Error> {
+        let layout = module_layouts.get(&self.module)?;
+        if self.value as u64 > layout.max_value() {
+            bail!("value {} does not fit the value field, the maximum is {}", self.value, layout.max_value());
+        }
+
+        let catalog = CommandCatalog::new(layout);
+        match (self.command(&catalog)?, &self.operation) {
+            (Some(_), Some(_)) => bail!("a step sets either 'command' or 'operation', not both"),
+            (None, None) => bail!("a step needs a 'command' or an 'operation'"),
+            (None, Some(name)) => {
+                if self.wait.is_some() || self.timeout_ms.is_some() || self.stop_command.is_some() {
+                    bail!("operation '{}' defines its own completion, remove 'wait', 'timeout_ms' and 'stop_command'", name);
+                }
+                let operation = layout
+                    .operation(name)
+                    .ok_or_else(|| anyhow!("module '{}' has no operation '{}'", self.module, name))?;
+                Ok(StepAction::Operation { operation: operation.clone(), goal: self.value })
+            }
+            (Some(command), None) => match &self.wait {
+                None => {
+                    if self.timeout_ms.is_some() || self.stop_command.is_some() {
+                        bail!("'timeout_ms' and 'stop_command' only apply to a step with 'wait'");
+                    }
+                    if command as u64 > layout.max_command() {
+                        bail!("command {} does not fit the command field, the maximum is {}", command, layout.max_command());
+                    }
+                    catalog.check(command, self.value)?;
+                    Ok(StepAction::Command { command, value: self.value })
+                }
+                Some(wait) => {
+                    // a command waiting for a state is an operation defined inline
+                    let operation = OperationLayout {
+                        name: "recipe_step".to_string(),
+                        command,
+                        stop_command: self.stop_command,
+                        stop_value: 0,
+                        timeout_ms: self.timeout_ms.ok_or_else(|| anyhow!("a step with 'wait' needs 'timeout_ms'"))?,
+                        done: wait.clone(),
+                    };
+                    layout.validate_operation(&operation)?;
+                    catalog.check(command, self.value)?;
+                    Ok(StepAction::Operation { operation, goal: self.value })
+                }
+            },
+        }
+    }
+
+    /// code of `command`, a name is looked up in the command catalog
+    fn command(&self, catalog: &CommandCatalog) -> Result<Option<u8>, Error> {
+        match &self.command {
+            None => Ok(None),
+            Some(command) => catalog
+                .code(command)
+                .map(Some)
+                .ok_or_else(|| anyhow!("module '{}' has no command {}", self.module, command)),
+        }
+    }
+
+    /// short description used in feedback and logs
+    pub fn describe(&self) -> String {
+        match (&self.operation, &self.command) {
+            (Some(operation), _) => format!("{} {} {}", self.module, operation, self.value),
+            (None, Some(CommandRef::Code(command))) => format!("{} command {} value {}", self.module, command, self.value),
+            (None, Some(CommandRef::Name(command))) => format!("{} {} {}", self.module, command, self.value),
+            (None, None) => self.module.clone(),
+        }
+    }
+}
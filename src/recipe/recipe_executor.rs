@@ -0,0 +1,446 @@
+use super::{Recipes, RecipeStep, StepAction};
+use crate::command_tracker::CommandError;
+use crate::converter_registry::ConverterRegistry;
+use crate::module_layout::ModuleLayouts;
+use crate::module_operation::OperationError;
+use std::{collections::HashMap, fmt, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
+use anyhow::{Context, Error, Result};
+use log::{info, warn};
+
+/// reason a recipe did not complete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipeError {
+    /// no recipe with this name was loaded
+    UnknownRecipe { recipe: String },
+    /// another recipe is running
+    Busy { running: String },
+    /// canceled before `step` (counted from 1) completed
+    Canceled { step: usize },
+    /// `step` (counted from 1) failed
+    StepFailed { step: usize, description: String, reason: String },
+}
+
+impl RecipeError {
+    /// short name of the failure class, stable for counting and logging
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RecipeError::UnknownRecipe { .. } => "unknown_recipe",
+            RecipeError::Busy { .. } => "busy",
+            RecipeError::Canceled { .. } => "canceled",
+            RecipeError::StepFailed { .. } => "step_failed",
+        }
+    }
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecipeError::UnknownRecipe { recipe } => write!(f, "recipe '{}' is not defined", recipe),
+            RecipeError::Busy { running } => write!(f, "recipe '{}' is still running", running),
+            RecipeError::Canceled { step } => write!(f, "recipe canceled at step {}", step),
+            RecipeError::StepFailed { step, description, reason } => {
+                write!(f, "step {} ({}) failed: {}", step, description, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecipeError {}
+
+/// reservation of the executor for one recipe, returned by `begin` and consumed by `run`
+pub struct RecipeRun {
+    name: String,
+    steps: Arc<Vec<(RecipeStep, StepAction)>>,
+    canceled: Arc<AtomicBool>,
+}
+
+struct ActiveRecipe {
+    name: String,
+    canceled: Arc<AtomicBool>,
+    /// module and operation of the running step
+    operation: Option<(String, String)>,
+}
+
+/// executes recipes through the converters, one recipe at a time
+pub struct RecipeExecutor {
+    converters: Arc<ConverterRegistry>,
+    recipes: HashMap<String, Arc<Vec<(RecipeStep, StepAction)>>>,
+    active: Mutex<Option<ActiveRecipe>>,
+}
+
+impl RecipeExecutor {
+    pub fn new(converters: Arc<ConverterRegistry>, recipes: &Recipes, module_layouts: &ModuleLayouts) -> Result<Self, Error> {
+        let mut resolved = HashMap::new();
+        for recipe in &recipes.recipe {
+            let steps = recipe.step
+                .iter()
+                .map(|step| Ok((step.clone(), step.action(module_layouts)?)))
+                .collect::<Result<Vec<_>, Error>>()
+                .with_context(|| format!("[{}] invalid recipe", recipe.name))?;
+            resolved.insert(recipe.name.clone(), Arc::new(steps));
+        }
+        Ok(Self {
+            converters,
+            recipes: resolved,
+            active: Mutex::new(None),
+        })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.recipes.keys().map(String::as_str)
+    }
+
+    /// name of the running recipe
+    pub fn running(&self) -> Option<String> {
+        self.active
+            .lock()
+            .ok()
+            .and_then(|active| active.as_ref().map(|active| active.name.clone()))
+    }
+
+    /// reserve the executor for `recipe`
+    pub fn begin(&self, recipe: &str) -> Result<RecipeRun, RecipeError> {
+        let steps = self.recipes
+            .get(recipe)
+            .cloned()
+            .ok_or_else(|| RecipeError::UnknownRecipe { recipe: recipe.to_string() })?;
+
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(running) = active.as_ref() {
+            return Err(RecipeError::Busy { running: running.name.clone() });
+        }
+        let canceled = Arc::new(AtomicBool::new(false));
+        *active = Some(ActiveRecipe { name: recipe.to_string(), canceled: canceled.clone(), operation: None });
+        Ok(RecipeRun { name: recipe.to_string(), steps, canceled })
+    }
+
+    /// execute the steps of `run` in order, `feedback` is called with the step index
+    /// (counted from 1), the step count and the step before each step starts
+    pub fn run<F>(&self, run: RecipeRun, feedback: F) -> Result<(), RecipeError>
+    where
+        F: Fn(usize, usize, &RecipeStep),
+    {
+        let result = self.run_steps(&run, feedback);
+        if let Ok(mut active) = self.active.lock() {
+            *active = None;
+        }
+        match &result {
+            Ok(()) => info!("'{}' completed", run.name),
+            Err(e) => warn!("'{}' failed: {}", run.name, e),
+        }
+        result
+    }
+
+    /// cancel the running recipe, its running operation is canceled, a running command stops
+    /// waiting for its acknowledgement and no further step starts.
+    /// returns whether a recipe was running
+    pub fn cancel(&self) -> bool {
+        let operation = match self.active.lock() {
+            Ok(active) => match active.as_ref() {
+                Some(active) => {
+                    active.canceled.store(true, Ordering::SeqCst);
+                    active.operation.clone()
+                }
+                None => return false,
+            },
+            Err(_) => return false,
+        };
+        if let Some((module, operation)) = operation {
+            if let Some(converter) = self.converters.get(&module) {
+                converter.cancel_operation(&operation);
+            }
+        }
+        true
+    }
+
+    fn run_steps<F>(&self, run: &RecipeRun, feedback: F) -> Result<(), RecipeError>
+    where
+        F: Fn(usize, usize, &RecipeStep),
+    {
+        let count = run.steps.len();
+        for (i, (step, action)) in run.steps.iter().enumerate() {
+            let index = i + 1;
+            let failed = |reason: String| RecipeError::StepFailed { step: index, description: step.describe(), reason };
+
+            let operation = match action {
+                StepAction::Operation { operation, .. } => Some((step.module.clone(), operation.name.clone())),
+                StepAction::Command { .. } => None,
+            };
+            if let Ok(mut active) = self.active.lock() {
+                if let Some(active) = active.as_mut() {
+                    active.operation = operation;
+                }
+            }
+            if run.canceled.load(Ordering::SeqCst) {
+                return Err(RecipeError::Canceled { step: index });
+            }
+
+            feedback(index, count, step);
+            info!("'{}' step {}/{}: {}", run.name, index, count, step.describe());
+
+            let converter = self.converters
+                .get(&step.module)
+                .ok_or_else(|| failed(format!("no converter registered for module '{}'", step.module)))?;
+
+            match action {
+                StepAction::Command { command, value } => match converter.execute_command(*command, *value, &run.canceled) {
+                    Ok(()) => {}
+                    Err(CommandError::Canceled) => return Err(RecipeError::Canceled { step: index }),
+                    Err(e) => return Err(failed(e.to_string())),
+                },
+                StepAction::Operation { operation, goal } => match converter.run_operation(operation, *goal, &run.canceled) {
+                    Ok(()) => {}
+                    Err(OperationError::Canceled) => return Err(RecipeError::Canceled { step: index }),
+                    Err(e) => return Err(failed(e.to_string())),
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CommandConfig, SimulatorConfig};
+    use crate::interlock::{InterlockGuard, Interlocks};
+    use crate::module_msg_converter::{DecodedValues, ModuleMsgConverter};
+    use crate::module_state::ModuleStates;
+    use crate::simulator::Simulator;
+    use crate::transport::{FrameTransport, InMemoryTransport, SimulatorTransport};
+    use std::{thread, time::{Duration, Instant}};
+
+    const STEPS: &str = r#"
+        [[recipe]]
+        name = "lights"
+
+        [[recipe.step]]
+        module = "light"
+        command = "on"
+        value = 1
+
+        [[recipe.step]]
+        module = "pdu"
+        command = "power_on"
+        value = 4
+
+        [[recipe.step]]
+        module = "light"
+        command = "off"
+
+        [[recipe]]
+        name = "cup"
+
+        [[recipe.step]]
+        module = "cup_holder"
+        operation = "move_to"
+        value = 1200
+
+        [[recipe.step]]
+        module = "light"
+        command = "on"
+        value = 1
+    "#;
+
+    fn layouts() -> ModuleLayouts {
+        ModuleLayouts::from_toml(include_str!("../../config/modules.toml")).unwrap()
+    }
+
+    /// converters of every module behind `transport`, started and receiving
+    fn converters(
+        transport: Arc<dyn FrameTransport>,
+        module_layouts: &ModuleLayouts,
+        command_config: &CommandConfig,
+        interlocks: &Interlocks,
+    ) -> Arc<ConverterRegistry> {
+        let context = rclrs::Context::new([]).unwrap();
+        let node = rclrs::create_node(&context, "recipe_executor_test").unwrap();
+        let states = Arc::new(ModuleStates::new());
+        let interlock = Arc::new(InterlockGuard::new(interlocks, module_layouts, states.clone()).unwrap());
+        let converters = ConverterRegistry::with_modules(
+            transport.clone(), node, module_layouts, command_config, states, interlock
+        ).unwrap();
+        converters.start_all().unwrap();
+
+        let converters = Arc::new(converters);
+        let dispatch = converters.clone();
+        transport.start_receiving(Box::new(move |namespace, frame| {
+            let _ = dispatch.dispatch(namespace, frame);
+        })).unwrap();
+        converters
+    }
+
+    /// executor of `STEPS` on an in memory link, commands fail after `timeout_ms`
+    fn executor(timeout_ms: u64) -> (RecipeExecutor, Arc<InMemoryTransport>) {
+        let module_layouts = layouts();
+        let transport = Arc::new(InMemoryTransport::new());
+        let command_config = CommandConfig { timeout_ms, retries: 0 };
+        let converters = converters(transport.clone(), &module_layouts, &command_config, &Interlocks::default());
+        let recipes = Recipes::from_toml(STEPS, &module_layouts).unwrap();
+        (RecipeExecutor::new(converters, &recipes, &module_layouts).unwrap(), transport)
+    }
+
+    /// answer every frame sent to a module of `namespaces` with `state`, or idle for a stop
+    /// command (code 0), until `done` is set. the frames of other modules stay unanswered
+    fn respond(transport: &Arc<InMemoryTransport>, namespaces: &[&str], state: u8, done: &Arc<AtomicBool>) -> thread::JoinHandle<()> {
+        let module_layouts = layouts();
+        let converters: HashMap<String, ModuleMsgConverter> = namespaces
+            .iter()
+            .map(|namespace| {
+                let layout = module_layouts.get(namespace).unwrap();
+                let converter = ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format());
+                (namespace.to_string(), converter)
+            })
+            .collect();
+        let (transport, done) = (transport.clone(), done.clone());
+        thread::spawn(move || {
+            let mut answered = 0;
+            while !done.load(Ordering::SeqCst) {
+                let sent = transport.sent_frames();
+                for (namespace, frame) in &sent[answered..] {
+                    if let Some(converter) = converters.get(namespace) {
+                        let state = match converter.decode_set_str(frame).unwrap() {
+                            (0, _) => 0,
+                            _ => state,
+                        };
+                        let frame = converter.encode_values(&DecodedValues { state, fields: Vec::new() }).unwrap();
+                        transport.receive_frame(namespace, &frame).unwrap();
+                    }
+                }
+                answered = sent.len();
+                thread::sleep(Duration::from_millis(1));
+            }
+        })
+    }
+
+    fn sent_namespaces(transport: &InMemoryTransport) -> Vec<String> {
+        transport.sent_frames().into_iter().map(|(namespace, _)| namespace).collect()
+    }
+
+    #[test]
+    fn runs_the_steps_in_order() {
+        let (executor, transport) = executor(1000);
+        let done = Arc::new(AtomicBool::new(false));
+        let responder = respond(&transport, &["light", "pdu"], 0, &done);
+
+        let steps = Mutex::new(Vec::new());
+        let run = executor.begin("lights").unwrap();
+        let result = executor.run(run, |index, count, step| steps.lock().unwrap().push((index, count, step.describe())));
+        done.store(true, Ordering::SeqCst);
+        responder.join().unwrap();
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            steps.into_inner().unwrap(),
+            vec![(1, 3, "light on 1".to_string()), (2, 3, "pdu power_on 4".to_string()), (3, 3, "light off 0".to_string())]
+        );
+        assert_eq!(sent_namespaces(&transport), vec!["light", "pdu", "light"]);
+        assert_eq!(executor.running(), None);
+    }
+
+    #[test]
+    fn a_failed_step_ends_the_recipe() {
+        let (executor, transport) = executor(50);
+        let done = Arc::new(AtomicBool::new(false));
+        // the pdu never answers
+        let responder = respond(&transport, &["light"], 0, &done);
+
+        let run = executor.begin("lights").unwrap();
+        let result = executor.run(run, |_, _, _| {});
+        done.store(true, Ordering::SeqCst);
+        responder.join().unwrap();
+
+        match result {
+            Err(RecipeError::StepFailed { step: 2, description, reason }) => {
+                assert_eq!(description, "pdu power_on 4");
+                assert!(reason.contains("did not answer"), "{}", reason);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(sent_namespaces(&transport), vec!["light", "pdu"]);
+        // the executor is free again
+        assert!(executor.begin("lights").is_ok());
+    }
+
+    #[test]
+    fn cancel_before_the_first_step_sends_nothing() {
+        let (executor, transport) = executor(1000);
+        let run = executor.begin("lights").unwrap();
+        assert_eq!(executor.begin("cup").err(), Some(RecipeError::Busy { running: "lights".to_string() }));
+        assert!(executor.cancel());
+
+        assert_eq!(executor.run(run, |_, _, _| {}), Err(RecipeError::Canceled { step: 1 }));
+        assert!(transport.sent_frames().is_empty());
+        assert!(!executor.cancel());
+    }
+
+    /// cancel the recipe once `count` frames were sent, returns how long `run` took after the cancel
+    fn cancel_after(executor: &RecipeExecutor, transport: &InMemoryTransport, recipe: &str, count: usize) -> (Result<(), RecipeError>, Duration) {
+        let run = executor.begin(recipe).unwrap();
+        let canceled_at = Mutex::new(None);
+        let result = thread::scope(|scope| {
+            scope.spawn(|| {
+                while transport.sent_frames().len() < count {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                *canceled_at.lock().unwrap() = Some(Instant::now());
+                assert!(executor.cancel());
+            });
+            executor.run(run, |_, _, _| {})
+        });
+        let elapsed = canceled_at.into_inner().unwrap().unwrap().elapsed();
+        (result, elapsed)
+    }
+
+    #[test]
+    fn cancel_stops_a_command_step_waiting_for_its_acknowledgement() {
+        // nothing answers, the command would wait the whole timeout
+        let (executor, transport) = executor(5000);
+        let (result, elapsed) = cancel_after(&executor, &transport, "lights", 1);
+
+        assert_eq!(result, Err(RecipeError::Canceled { step: 1 }));
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+        assert_eq!(sent_namespaces(&transport), vec!["light"]);
+    }
+
+    #[test]
+    fn cancel_stops_a_running_operation() {
+        let (executor, transport) = executor(1000);
+        let done = Arc::new(AtomicBool::new(false));
+        // the cup holder acknowledges with busy but never reaches its goal
+        let responder = respond(&transport, &["cup_holder"], 1, &done);
+        let (result, elapsed) = cancel_after(&executor, &transport, "cup", 1);
+        done.store(true, Ordering::SeqCst);
+        responder.join().unwrap();
+
+        assert_eq!(result, Err(RecipeError::Canceled { step: 1 }));
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+        // the move was followed by its stop command and the light step never started
+        let layout = layouts();
+        let layout = layout.get("cup_holder").unwrap();
+        let converter = ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format());
+        let commands: Vec<(u8, u16)> = transport
+            .sent_frames()
+            .iter()
+            .map(|(_, frame)| converter.decode_set_str(frame).unwrap())
+            .collect();
+        assert_eq!(commands, vec![(1, 1200), (0, 0)]);
+    }
+
+    #[test]
+    fn shipped_espresso_recipe_completes_on_the_simulator() {
+        let module_layouts = layouts();
+        let config = SimulatorConfig { period_ms: 50, ..SimulatorConfig::default() };
+        let transport = Arc::new(SimulatorTransport::new(Simulator::new(&module_layouts, &config), &config));
+        let interlocks = Interlocks::from_toml(include_str!("../../config/interlocks.toml"), &module_layouts).unwrap();
+        let converters = converters(transport, &module_layouts, &CommandConfig::default(), &interlocks);
+        let recipes = Recipes::from_toml(include_str!("../../config/recipes.toml"), &module_layouts).unwrap();
+        let executor = RecipeExecutor::new(converters, &recipes, &module_layouts).unwrap();
+
+        let steps = Mutex::new(Vec::new());
+        let run = executor.begin("espresso").unwrap();
+        let result = executor.run(run, |index, _, _| steps.lock().unwrap().push(index));
+        assert_eq!(result, Ok(()));
+        assert_eq!(steps.into_inner().unwrap(), vec![1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,74 @@
+use super::RecipeExecutor;
+use std::sync::Arc;
+use anyhow::{Error, Result};
+use log::{error, warn};
+use rclrs::{Node, Publisher, Service, Subscription};
+use obd_coffee_maker_interface::srv::{RunRecipe, RunRecipe_Request, RunRecipe_Response};
+use std_msgs::msg::{Empty as EmptyMsg, String as StringMsg};
+
+/// ROS side of the recipe executor, an action made like the one of a module operation,
+/// see `module_operation`
+pub struct RecipeServer {
+    /// `recipe/run`, starts the requested recipe, the response tells if it was accepted
+    pub run_service: Arc<Service<RunRecipe>>,
+    /// `recipe/feedback`, the step that is starting, e.g. `2/5 cup_holder move_to 120`
+    pub feedback_publisher: Arc<Publisher<StringMsg>>,
+    /// `recipe/result`, published once the recipe ended
+    pub result_publisher: Arc<Publisher<RunRecipe_Response>>,
+    /// `recipe/cancel`
+    pub cancel_subscription: Arc<Subscription<EmptyMsg>>,
+}
+
+impl RecipeServer {
+    pub fn start(node: &Arc<Node>, executor: Arc<RecipeExecutor>) -> Result<Self, Error> {
+        let feedback_publisher = node.create_publisher::<StringMsg>("recipe/feedback", rclrs::QOS_PROFILE_DEFAULT)?;
+        let result_publisher = node.create_publisher::<RunRecipe_Response>("recipe/result", rclrs::QOS_PROFILE_DEFAULT)?;
+
+        let executor_clone = executor.clone();
+        let feedback_clone = feedback_publisher.clone();
+        let result_clone = result_publisher.clone();
+        // the recipe runs on its own thread so the executor keeps serving the cancel topic
+        let run_service = node.create_service::<RunRecipe, _>(
+            "recipe/run",
+            move |_request_id: &rclrs::rmw_request_id_t, request: RunRecipe_Request| {
+                let run = match executor_clone.begin(&request.recipe) {
+                    Ok(run) => run,
+                    Err(e) => return RunRecipe_Response { success: false, message: e.to_string() },
+                };
+
+                let executor = executor_clone.clone();
+                let feedback_publisher = feedback_clone.clone();
+                let result_publisher = result_clone.clone();
+                std::thread::spawn(move || {
+                    let result = executor.run(run, |index, count, step| {
+                        let feedback = StringMsg { data: format!("{}/{} {}", index, count, step.describe()) };
+                        if let Err(e) = feedback_publisher.publish(feedback) {
+                            error!("Failed to publish feedback: {:?}", e);
+                        }
+                    });
+                    let response = match result {
+                        Ok(()) => RunRecipe_Response { success: true, message: String::new() },
+                        Err(e) => RunRecipe_Response { success: false, message: e.to_string() },
+                    };
+                    if let Err(e) = result_publisher.publish(response) {
+                        error!("Failed to publish result: {:?}", e);
+                    }
+                });
+
+                RunRecipe_Response { success: true, message: String::new() }
+            },
+        )?;
+
+        let cancel_subscription = node.create_subscription::<EmptyMsg, _>(
+            "recipe/cancel",
+            rclrs::QOS_PROFILE_DEFAULT,
+            move |_msg: EmptyMsg| {
+                if !executor.cancel() {
+                    warn!("cancel requested but no recipe is running");
+                }
+            },
+        )?;
+
+        Ok(Self { run_service, feedback_publisher, result_publisher, cancel_subscription })
+    }
+}
@@ -0,0 +1,274 @@
+use crate::config::SimulatorConfig;
+use crate::module_layout::{ModuleLayout, ModuleLayouts, OperationLayout};
+use crate::module_msg_converter::{DecodedValues, EncodeError, ModuleMsgConverter};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+use anyhow::{anyhow, Error, Result};
+use log::{debug, error, warn};
+
+/// values a freshly powered machine reports with the shipped layouts, every other
+/// bit field target starts at 0
+const INITIAL_VALUES: &[(&str, &str, i64)] = &[
+    ("coffee_feeder", "water_level", 3),
+    ("capsule_feeder", "capsule_status_list[0]", 1),
+    ("capsule_feeder", "capsule_status_list[1]", 1),
+    ("capsule_feeder", "capsule_status_list[2]", 1),
+    ("capsule_feeder", "capsule_status_list[3]", 1),
+    ("capsule_feeder", "capsule_status_list[4]", 1),
+    ("capsule_feeder", "capsule_status_list[5]", 1),
+    ("capsule_feeder", "home_detect", 1),
+    ("cup_holder", "cup_detect", 1),
+    ("cup_holder", "cup_stock", 3),
+    ("Tank", "water_quantity", 1500),
+    ("pdu", "coffee_pwr", 1),
+    ("pdu", "capsule_pwr", 1),
+    ("pdu", "cup_pwr", 1),
+    ("pdu", "tank_pwr", 1),
+    ("pdu", "light_pwr", 1),
+    ("pdu", "voltage", 24000),
+    ("pdu", "current", 1200),
+];
+
+/// `(namespace, frame)` of a `/set` frame sent to the simulator
+pub type SetFrame = (String, String);
+
+/// `state` reported while a simulated operation is moving
+const STATE_BUSY: u8 = 1;
+const STATE_IDLE: u8 = 0;
+
+/// field of a running operation moving towards its goal at the speed of the module
+struct Motion {
+    field: String,
+    goal: i64,
+    /// part of a unit travelled but not applied yet, keeps the speed exact on short ticks
+    travel: f64,
+}
+
+/// module answering `/set` frames like the firmware would: every frame is acknowledged
+/// with the current state and the command of an operation moves its `done.field`
+/// towards the goal until the operation completes or its stop command arrives
+pub struct SimulatedModule {
+    name: String,
+    converter: ModuleMsgConverter,
+    operations: Vec<OperationLayout>,
+    /// range of every bit field target, values are clamped so they can be encoded
+    ranges: HashMap<String, (i64, i64)>,
+    values: DecodedValues,
+    motion: Option<Motion>,
+    /// units per second
+    speed: f64,
+}
+
+impl SimulatedModule {
+    pub fn new(layout: &ModuleLayout, config: &SimulatorConfig) -> Self {
+        let mut ranges = HashMap::new();
+        let mut values = DecodedValues { state: STATE_IDLE, fields: Vec::new() };
+        for field in &layout.output.payload {
+            for bit_field in &field.bits {
+                let max = (1i64 << bit_field.width.min(32)) - 1;
+                let range = if field.signed {
+                    (-max, max)
+                } else if bit_field.signed {
+                    (-(max / 2) - 1, max / 2)
+                } else {
+                    (0, max)
+                };
+                ranges.insert(bit_field.target().to_string(), range);
+                values.set(bit_field.target(), 0);
+            }
+        }
+        for (_, target, value) in INITIAL_VALUES.iter().filter(|(module, _, _)| *module == layout.name) {
+            if ranges.contains_key(*target) {
+                values.set(target, *value);
+            }
+        }
+
+        Self {
+            name: layout.name.clone(),
+            converter: ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format()),
+            operations: layout.operation.clone(),
+            ranges,
+            values,
+            motion: None,
+            speed: config.speed as f64,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn values(&self) -> &DecodedValues {
+        &self.values
+    }
+
+    /// apply a `/set` frame and return the acknowledging `/get` frame
+    pub fn handle_set(&mut self, frame: &str) -> Result<String, Error> {
+        let (command, value) = self.converter
+            .decode_set_str(frame)
+            .map_err(|e| anyhow!("[{}] invalid /set frame '{}': {}", self.name, frame, e))?;
+        debug!(target: &self.name, "simulated command {} value {}", command, value);
+
+        if let Some(operation) = self.operations.iter().find(|operation| operation.command == command) {
+            let goal = operation.done.value.unwrap_or(value as i64);
+            let goal = match self.ranges.get(&operation.done.field) {
+                Some((min, max)) => goal.clamp(*min, *max),
+                None => goal,
+            };
+            self.motion = Some(Motion { field: operation.done.field.clone(), goal, travel: 0.0 });
+            self.values.state = STATE_BUSY;
+        } else if self.operations.iter().any(|operation| operation.stop_command == Some(command)) {
+            self.stop();
+        }
+
+        // a `state` goal is reached at once, the acknowledgement already reports it
+        if let Some(Motion { goal, .. }) = self.motion.as_ref().filter(|motion| motion.field == "state") {
+            let goal = *goal;
+            self.motion = None;
+            self.values.set("state", goal);
+        }
+
+        Ok(self.frame()?)
+    }
+
+    /// advance a running operation by the distance travelled in `elapsed`
+    pub fn tick(&mut self, elapsed: Duration) {
+        let speed = self.speed;
+        let motion = match &mut self.motion {
+            Some(motion) => motion,
+            None => return,
+        };
+        motion.travel += speed * elapsed.as_secs_f64();
+        let current = self.values.get(&motion.field).unwrap_or(0);
+        let distance = motion.goal - current;
+        let step = (motion.travel as i64).min(distance.abs());
+        motion.travel -= step as f64;
+        let next = current + step * distance.signum();
+        let (field, goal) = (motion.field.clone(), motion.goal);
+        self.values.set(&field, next);
+        if next == goal {
+            self.stop();
+        }
+    }
+
+    fn stop(&mut self) {
+        self.motion = None;
+        self.values.state = STATE_IDLE;
+    }
+
+    /// `/get` frame of the current state
+    pub fn frame(&self) -> Result<String, EncodeError> {
+        self.converter.encode_values(&self.values)
+    }
+}
+
+/// every module of the layouts simulated together, see `SimulatedModule`
+pub struct Simulator {
+    modules: BTreeMap<String, SimulatedModule>,
+}
+
+impl Simulator {
+    pub fn new(layouts: &ModuleLayouts, config: &SimulatorConfig) -> Self {
+        let modules = layouts.module
+            .iter()
+            .map(|layout| (layout.name.clone(), SimulatedModule::new(layout, config)))
+            .collect();
+        Self { modules }
+    }
+
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        self.modules.keys().map(String::as_str)
+    }
+
+    pub fn module(&self, namespace: &str) -> Option<&SimulatedModule> {
+        self.modules.get(namespace)
+    }
+
+    /// apply a `/set` frame to the module of `namespace`, returns the acknowledging `/get` frame
+    pub fn handle_set(&mut self, namespace: &str, frame: &str) -> Result<String, Error> {
+        match self.modules.get_mut(namespace) {
+            Some(module) => module.handle_set(frame),
+            None => Err(anyhow!("no simulated module '{}'", namespace)),
+        }
+    }
+
+    pub fn tick(&mut self, elapsed: Duration) {
+        self.modules.values_mut().for_each(|module| module.tick(elapsed));
+    }
+
+    /// answer every frame of `incoming` and send the state of every module
+    /// each `period` through `emit(namespace, frame)`, returns once `incoming` is closed
+    pub fn run<F: FnMut(&str, &str)>(&mut self, period: Duration, incoming: Receiver<SetFrame>, mut emit: F) {
+        let mut next_tick = Instant::now() + period;
+        loop {
+            match incoming.recv_timeout(next_tick.saturating_duration_since(Instant::now())) {
+                Ok((namespace, frame)) => match self.handle_set(&namespace, &frame) {
+                    Ok(reply) => emit(&namespace, &reply),
+                    Err(e) => warn!("{:#}", e),
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    self.tick(period);
+                    for (namespace, module) in &self.modules {
+                        match module.frame() {
+                            Ok(frame) => emit(namespace, &frame),
+                            Err(e) => error!(target: namespace, "cannot encode simulated state: {}", e),
+                        }
+                    }
+                    next_tick += period;
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layouts() -> ModuleLayouts {
+        ModuleLayouts::from_toml(include_str!("../config/modules.toml")).unwrap()
+    }
+
+    fn send(simulator: &mut Simulator, layouts: &ModuleLayouts, namespace: &str, command: u8, value: u16) -> DecodedValues {
+        let layout = layouts.get(namespace).unwrap();
+        let converter = ModuleMsgConverter::new(layout.name.clone(), layout.input_format(), layout.output_format());
+        let reply = simulator.handle_set(namespace, &converter.encode_set_str(command, value).unwrap()).unwrap();
+        converter.decode_get_values(&reply).unwrap()
+    }
+
+    #[test]
+    fn operations_of_the_shipped_layouts_reach_their_goal() {
+        let layouts = layouts();
+        let mut simulator = Simulator::new(&layouts, &SimulatorConfig::default());
+        for (namespace, field, goal) in [("capsule_feeder", "capsule_selector_pos", 2), ("cup_holder", "position", 1200)] {
+            assert_eq!(send(&mut simulator, &layouts, namespace, 1, goal as u16).state, STATE_BUSY);
+
+            simulator.tick(Duration::from_secs(10));
+            let values = simulator.module(namespace).unwrap().values();
+            assert_eq!((values.get(field), values.state), (Some(goal), STATE_IDLE), "{}", namespace);
+        }
+    }
+
+    #[test]
+    fn operation_moves_at_the_configured_speed() {
+        let layouts = layouts();
+        let config = SimulatorConfig { speed: 100, ..SimulatorConfig::default() };
+        let mut simulator = Simulator::new(&layouts, &config);
+        send(&mut simulator, &layouts, "cup_holder", 1, 1200);
+
+        // short ticks travel less than a unit each, the fractions must add up
+        for _ in 0..200 {
+            simulator.tick(Duration::from_millis(5));
+        }
+        let values = simulator.module("cup_holder").unwrap().values();
+        assert_eq!((values.get("position"), values.state), (Some(100), STATE_BUSY));
+
+        simulator.tick(Duration::from_secs(11));
+        let values = simulator.module("cup_holder").unwrap().values();
+        assert_eq!((values.get("position"), values.state), (Some(1200), STATE_IDLE));
+    }
+}
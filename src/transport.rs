@@ -0,0 +1,80 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Mutex};
+use anyhow::{Error, anyhow};
+
+pub mod capture_transport;
+pub mod memory_transport;
+pub mod mqtt_transport;
+pub mod serial_transport;
+pub mod simulator_transport;
+
+pub use capture_transport::{read_capture, CaptureTransport, CapturedFrame, FrameDirection};
+pub use memory_transport::InMemoryTransport;
+pub use mqtt_transport::MqttTransport;
+pub use serial_transport::SerialTransport;
+pub use simulator_transport::SimulatorTransport;
+
+/// callback receiving `(namespace, frame)` for every frame sent by a module
+pub type FrameHandler = Box<dyn Fn(&str, &str) + Send>;
+
+/// callback receiving `true` when the link to the modules comes up and `false` when it drops
+pub type ConnectionHandler = Box<dyn Fn(bool) + Send>;
+
+/// link carrying `@XXX...LRC#` frames between the converters and the modules
+pub trait FrameTransport: Send + Sync {
+    /// send a `/set` frame to the module of `namespace`
+    fn send_frame(&self, namespace: &str, frame: &str) -> Result<(), Error>;
+
+    /// start accepting `/get` frames of the module of `namespace`
+    fn subscribe(&self, namespace: &str) -> Result<(), Error>;
+
+    /// spawn the thread delivering incoming frames to `handler`, can only be called once
+    fn start_receiving(&self, handler: FrameHandler) -> Result<(), Error>;
+
+    /// report every change of the link state to `handler`, starting with the current state.
+    /// links without a connection of their own are up as soon as they exist
+    fn watch_connection(&self, handler: ConnectionHandler) -> Result<(), Error> {
+        handler(true);
+        Ok(())
+    }
+
+    /// current link state, see `watch_connection`
+    fn is_connected(&self) -> bool {
+        true
+    }
+}
+
+/// current link state shared between a transport and its receiving thread
+#[derive(Default)]
+pub struct ConnectionState {
+    connected: AtomicBool,
+    handler: Mutex<Option<ConnectionHandler>>,
+}
+
+impl ConnectionState {
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// store the new state and notify the handler when it changed
+    pub fn set(&self, connected: bool) {
+        let previous = self.connected.swap(connected, Ordering::SeqCst);
+        if previous != connected {
+            if let Ok(handler) = self.handler.lock() {
+                if let Some(handler) = handler.as_ref() {
+                    handler(connected);
+                }
+            }
+        }
+    }
+
+    pub fn watch(&self, handler: ConnectionHandler) -> Result<(), Error> {
+        match self.handler.lock() {
+            Ok(mut current) => {
+                handler(self.is_connected());
+                *current = Some(handler);
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Failed to acquire connection handler lock")),
+        }
+    }
+}
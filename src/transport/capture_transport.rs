@@ -0,0 +1,223 @@
+use super::{ConnectionHandler, FrameHandler, FrameTransport};
+use std::{
+    fmt, fs::{self, File, OpenOptions}, io::{BufWriter, Write}, path::Path,
+    sync::{Arc, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use anyhow::{Result, Context, Error, anyhow, bail};
+use log::error;
+
+/// direction of a captured frame, named after the topic suffix carrying it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// sent by the driver to a module
+    Set,
+    /// sent by a module to the driver
+    Get,
+}
+
+impl fmt::Display for FrameDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameDirection::Set => write!(f, "set"),
+            FrameDirection::Get => write!(f, "get"),
+        }
+    }
+}
+
+/// one line of a capture file: `<unix time>\t<set|get>\t<namespace>\t<frame>`, a tab,
+/// line break or backslash inside the namespace or frame is written as `\t`, `\n`, `\r`
+/// or `\\`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    /// time since the unix epoch
+    pub timestamp: Duration,
+    pub direction: FrameDirection,
+    pub namespace: String,
+    pub frame: String,
+}
+
+impl CapturedFrame {
+    pub fn new(direction: FrameDirection, namespace: &str, frame: &str) -> Self {
+        Self {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+            direction,
+            namespace: namespace.to_string(),
+            frame: frame.to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        let parts: Vec<&str> = line.splitn(4, '\t').collect();
+        if parts.len() != 4 {
+            bail!("expected '<time>\\t<set|get>\\t<namespace>\\t<frame>', got '{}'", line);
+        }
+        let timestamp = parts[0]
+            .parse::<f64>()
+            .ok()
+            .filter(|secs| secs.is_finite() && *secs >= 0.0)
+            .map(Duration::from_secs_f64)
+            .ok_or_else(|| anyhow!("invalid timestamp '{}'", parts[0]))?;
+        let direction = match parts[1] {
+            "set" => FrameDirection::Set,
+            "get" => FrameDirection::Get,
+            other => bail!("invalid direction '{}', expected 'set' or 'get'", other),
+        };
+        let namespace = unescape(parts[2]).context("invalid namespace")?;
+        let frame = unescape(parts[3]).context("invalid frame")?;
+        Ok(Self { timestamp, direction, namespace, frame })
+    }
+}
+
+impl fmt::Display for CapturedFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f, "{}.{:06}\t{}\t{}\t{}",
+            self.timestamp.as_secs(), self.timestamp.subsec_micros(), self.direction,
+            escape(&self.namespace), escape(&self.frame)
+        )
+    }
+}
+
+/// keep a captured field on its line and out of the neighbouring fields
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(field: &str) -> Result<String, Error> {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => bail!("unknown escape '\\{}'", other),
+            None => bail!("'\\' at the end of '{}'", field),
+        }
+    }
+    Ok(unescaped)
+}
+
+/// read every frame of a capture file, empty lines and `#` comments are skipped
+pub fn read_capture<P: AsRef<Path>>(path: P) -> Result<Vec<CapturedFrame>, Error> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("cannot read capture file '{}'", path.display()))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            CapturedFrame::parse(line)
+                .with_context(|| format!("invalid capture file '{}' line {}", path.display(), number + 1))
+        })
+        .collect()
+}
+
+/// records every frame passing through `inner` to a capture file, the link itself is untouched
+pub struct CaptureTransport {
+    inner: Arc<dyn FrameTransport>,
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl CaptureTransport {
+    /// append to the capture file at `path`, created when missing
+    pub fn create<P: AsRef<Path>>(inner: Arc<dyn FrameTransport>, path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("cannot open capture file '{}'", path.display()))?;
+        Ok(Self { inner, file: Arc::new(Mutex::new(BufWriter::new(file))) })
+    }
+}
+
+impl FrameTransport for CaptureTransport {
+    fn send_frame(&self, namespace: &str, frame: &str) -> Result<(), Error> {
+        record(&self.file, CapturedFrame::new(FrameDirection::Set, namespace, frame));
+        self.inner.send_frame(namespace, frame)
+    }
+
+    fn subscribe(&self, namespace: &str) -> Result<(), Error> {
+        self.inner.subscribe(namespace)
+    }
+
+    fn start_receiving(&self, handler: FrameHandler) -> Result<(), Error> {
+        let file = self.file.clone();
+        self.inner.start_receiving(Box::new(move |namespace, frame| {
+            record(&file, CapturedFrame::new(FrameDirection::Get, namespace, frame));
+            handler(namespace, frame);
+        }))
+    }
+
+    fn watch_connection(&self, handler: ConnectionHandler) -> Result<(), Error> {
+        self.inner.watch_connection(handler)
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+}
+
+/// a failing capture never stops the frame, flushed per line so a crash keeps the capture
+fn record(file: &Mutex<BufWriter<File>>, captured: CapturedFrame) {
+    match file.lock() {
+        Ok(mut file) => {
+            if let Err(e) = writeln!(file, "{}", captured).and_then(|_| file.flush()) {
+                error!("Failed to write capture file: {}", e);
+            }
+        }
+        Err(_) => error!("Failed to acquire capture file lock"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_round_trip() {
+        let captured = CapturedFrame {
+            timestamp: Duration::from_micros(1_700_000_000_123_456),
+            direction: FrameDirection::Get,
+            namespace: "Tank".to_string(),
+            frame: "@TNK\t00\\01\r\n#".to_string(),
+        };
+        let line = captured.to_string();
+        assert_eq!(line, "1700000000.123456\tget\tTank\t@TNK\\t00\\\\01\\r\\n#");
+        assert_eq!(line.lines().count(), 1);
+        let parsed = CapturedFrame::parse(&line).unwrap();
+        // the timestamp is written with microsecond resolution
+        assert_eq!(parsed.timestamp.as_micros(), captured.timestamp.as_micros());
+        assert_eq!(CapturedFrame { timestamp: captured.timestamp, ..parsed }, captured);
+    }
+
+    #[test]
+    fn rejects_broken_lines() {
+        for line in [
+            "1.0\tget\tTank",
+            "x\tget\tTank\t@TNK#",
+            "1.0\tput\tTank\t@TNK#",
+            "1.0\tget\tTank\t@TNK\\x#",
+            "1.0\tget\tTank\t@TNK#\\",
+        ] {
+            assert!(CapturedFrame::parse(line).is_err(), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use super::{FrameHandler, FrameTransport};
+use std::{collections::HashSet, sync::Mutex};
+use anyhow::{Result, Error, anyhow};
+
+/// frames stay in process, sent frames are recorded and replies are injected by hand,
+/// used to exercise the converters without a broker or a serial port
+#[derive(Default)]
+pub struct InMemoryTransport {
+    sent: Mutex<Vec<(String, String)>>,
+    subscribed: Mutex<HashSet<String>>,
+    handler: Mutex<Option<FrameHandler>>,
+}
+
+impl InMemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `(namespace, frame)` of every frame sent so far
+    pub fn sent_frames(&self) -> Vec<(String, String)> {
+        self.sent.lock().map(|sent| sent.clone()).unwrap_or_default()
+    }
+
+    /// return and forget the frames sent so far
+    pub fn take_sent_frames(&self) -> Vec<(String, String)> {
+        self.sent.lock().map(|mut sent| std::mem::take(&mut *sent)).unwrap_or_default()
+    }
+
+    pub fn is_subscribed(&self, namespace: &str) -> bool {
+        self.subscribed
+            .lock()
+            .map(|subscribed| subscribed.contains(namespace))
+            .unwrap_or(false)
+    }
+
+    /// deliver a frame as if the module of `namespace` had sent it, frames of
+    /// namespaces nobody subscribed to are dropped like on a real link
+    pub fn receive_frame(&self, namespace: &str, frame: &str) -> Result<bool, Error> {
+        if !self.is_subscribed(namespace) {
+            return Ok(false);
+        }
+        match self.handler.lock() {
+            Ok(handler) => match handler.as_ref() {
+                Some(handler) => {
+                    handler(namespace, frame);
+                    Ok(true)
+                }
+                None => Err(anyhow!("in memory transport is not receiving")),
+            },
+            Err(_) => Err(anyhow!("Failed to acquire in memory handler lock")),
+        }
+    }
+}
+
+impl FrameTransport for InMemoryTransport {
+    fn send_frame(&self, namespace: &str, frame: &str) -> Result<(), Error> {
+        match self.sent.lock() {
+            Ok(mut sent) => {
+                sent.push((namespace.to_string(), frame.to_string()));
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Failed to acquire in memory frame lock")),
+        }
+    }
+
+    fn subscribe(&self, namespace: &str) -> Result<(), Error> {
+        match self.subscribed.lock() {
+            Ok(mut subscribed) => {
+                subscribed.insert(namespace.to_string());
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Failed to acquire in memory subscription lock")),
+        }
+    }
+
+    fn start_receiving(&self, handler: FrameHandler) -> Result<(), Error> {
+        let mut current = self.handler
+            .lock()
+            .map_err(|_| anyhow!("Failed to acquire in memory handler lock"))?;
+        if current.is_some() {
+            return Err(anyhow!("in memory transport is already receiving"));
+        }
+        *current = Some(handler);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn records_sent_frames() {
+        let transport = InMemoryTransport::new();
+        transport.send_frame("Tank", "@TNK0001#").unwrap();
+        transport.send_frame("light", "@LIT0002#").unwrap();
+        assert_eq!(transport.sent_frames().len(), 2);
+        assert_eq!(
+            transport.take_sent_frames(),
+            vec![("Tank".to_string(), "@TNK0001#".to_string()), ("light".to_string(), "@LIT0002#".to_string())]
+        );
+        assert!(transport.sent_frames().is_empty());
+    }
+
+    #[test]
+    fn delivers_frames_of_subscribed_namespaces_only() {
+        let transport = InMemoryTransport::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = received.clone();
+        transport
+            .start_receiving(Box::new(move |namespace: &str, frame: &str| {
+                received_clone.lock().unwrap().push((namespace.to_string(), frame.to_string()));
+            }))
+            .unwrap();
+        transport.subscribe("Tank").unwrap();
+
+        assert!(transport.receive_frame("Tank", "@TNK0001#").unwrap());
+        assert!(!transport.receive_frame("light", "@LIT0002#").unwrap());
+        assert_eq!(*received.lock().unwrap(), vec![("Tank".to_string(), "@TNK0001#".to_string())]);
+    }
+
+    #[test]
+    fn receives_only_once_started() {
+        let transport = InMemoryTransport::new();
+        transport.subscribe("Tank").unwrap();
+        assert!(transport.receive_frame("Tank", "@TNK0001#").is_err());
+        transport.start_receiving(Box::new(|_: &str, _: &str| {})).unwrap();
+        assert!(transport.start_receiving(Box::new(|_: &str, _: &str| {})).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use super::{ConnectionHandler, ConnectionState, FrameHandler, FrameTransport};
+use crate::config::MqttConfig;
+use std::{collections::HashSet, sync::{Arc, Mutex}, time::Duration};
+use anyhow::{Result, Error, anyhow};
+use log::{error, info, warn};
+use rumqttc::{Client, ClientError, Connection, Event, Packet, QoS, TrySendError};
+
+/// frames travel as payload of `<namespace>/set` and `<namespace>/get` topics
+pub struct MqttTransport {
+    client: Mutex<Client>,
+    connection: Mutex<Option<Connection>>,
+    /// namespaces whose `/get` topic is restored after every clean session reconnect
+    subscribed: Arc<Mutex<HashSet<String>>>,
+    state: Arc<ConnectionState>,
+    reconnect_initial: Duration,
+    reconnect_max: Duration,
+}
+
+impl MqttTransport {
+    pub fn new(config: &MqttConfig) -> Self {
+        let (client, connection) = Client::new(config.mqtt_options(), config.request_capacity);
+        Self {
+            client: Mutex::new(client),
+            connection: Mutex::new(Some(connection)),
+            subscribed: Arc::new(Mutex::new(HashSet::new())),
+            state: Arc::new(ConnectionState::default()),
+            reconnect_initial: Duration::from_millis(config.reconnect_initial_ms),
+            reconnect_max: Duration::from_millis(config.reconnect_max_ms),
+        }
+    }
+}
+
+impl FrameTransport for MqttTransport {
+    /// queue the frame without waiting, fails at once while the request channel is full
+    /// because the broker is unreachable, the caller may be an executor callback
+    fn send_frame(&self, namespace: &str, frame: &str) -> Result<(), Error> {
+        match self.client.lock() {
+            Ok(mut client) => client
+                .try_publish(format!("{}/set", namespace), QoS::AtLeastOnce, false, frame.as_bytes().to_vec())
+                .map_err(|e| match e {
+                    ClientError::TryRequest(TrySendError::Full(_)) => {
+                        anyhow!("MQTT request queue is full, the broker does not take requests")
+                    }
+                    ClientError::TryRequest(TrySendError::Closed(_)) => anyhow!("MQTT event loop has stopped"),
+                    e => anyhow!("cannot publish to MQTT: {}", e),
+                }),
+            Err(_) => Err(anyhow!("Failed to acquire MQTT client lock")),
+        }
+    }
+
+    fn subscribe(&self, namespace: &str) -> Result<(), Error> {
+        match self.subscribed.lock() {
+            Ok(mut subscribed) => {
+                subscribed.insert(namespace.to_string());
+            }
+            Err(_) => return Err(anyhow!("Failed to acquire MQTT subscription lock")),
+        }
+        match self.client.lock() {
+            Ok(mut client) => {
+                client.subscribe(format!("{}/get", namespace), QoS::AtLeastOnce)?;
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Failed to acquire MQTT client lock for subscription")),
+        }
+    }
+
+    fn start_receiving(&self, handler: FrameHandler) -> Result<(), Error> {
+        let mut connection = self.connection
+            .lock()
+            .map_err(|_| anyhow!("Failed to acquire MQTT connection lock"))?
+            .take()
+            .ok_or_else(|| anyhow!("MQTT transport is already receiving"))?;
+
+        // own handle, the event loop never waits for the lock of the shared one
+        let mut client = match self.client.lock() {
+            Ok(client) => client.clone(),
+            Err(_) => return Err(anyhow!("Failed to acquire MQTT client lock")),
+        };
+        let subscribed = self.subscribed.clone();
+        let state = self.state.clone();
+        let reconnect_initial = self.reconnect_initial;
+        let reconnect_max = self.reconnect_max;
+
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async {
+                let mut backoff = reconnect_initial;
+                // the subscriptions of the first session are still queued by `subscribe`
+                let mut reconnect = false;
+                loop {
+                    match connection.eventloop.poll().await {
+                        Ok(Event::Incoming(Packet::ConnAck(connack))) => {
+                            info!("connected to broker");
+                            backoff = reconnect_initial;
+                            // a clean session forgets every subscription of the previous one
+                            if reconnect && !connack.session_present {
+                                resubscribe(&mut client, &subscribed);
+                            }
+                            reconnect = true;
+                            state.set(true);
+                        }
+                        Ok(Event::Incoming(Packet::Publish(publish))) => {
+                            // Extract namespace from topic
+                            let parts: Vec<&str> = publish.topic.split('/').collect();
+                            if parts.len() != 2 || parts[1] != "get" {
+                                warn!("incorrect format 'name/get' mqtt topic: {}", publish.topic);
+                                continue;
+                            }
+
+                            if let Ok(payload) = String::from_utf8(publish.payload.to_vec()) {
+                                handler(parts[0], &payload);
+                            } else {
+                                warn!("cannot convert bytes to String");
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            state.set(false);
+                            // the next poll reconnects, wait so an unreachable broker is not hammered
+                            warn!("connection error: {}, reconnect in {} ms", e, backoff.as_millis());
+                            tokio::time::sleep(backoff).await;
+                            backoff = (backoff * 2).min(reconnect_max);
+                        }
+                    }
+                }
+            });
+        });
+
+        Ok(())
+    }
+
+    fn watch_connection(&self, handler: ConnectionHandler) -> Result<(), Error> {
+        self.state.watch(handler)
+    }
+
+    fn is_connected(&self) -> bool {
+        self.state.is_connected()
+    }
+}
+
+/// queue a `/get` subscription for every namespace subscribed so far, the event loop
+/// consumes the queue so it must not block on a full request channel
+fn resubscribe(client: &mut Client, subscribed: &Mutex<HashSet<String>>) {
+    let namespaces: Vec<String> = match subscribed.lock() {
+        Ok(subscribed) => subscribed.iter().cloned().collect(),
+        Err(_) => {
+            error!("Failed to acquire MQTT subscription lock, cannot resubscribe");
+            return;
+        }
+    };
+    for namespace in namespaces {
+        if let Err(e) = client.try_subscribe(format!("{}/get", namespace), QoS::AtLeastOnce) {
+            error!("Failed to resubscribe '{}/get': {:?}", namespace, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn send_fails_at_once_without_broker() {
+        let config = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            client_id: "send-test".to_string(),
+            request_capacity: 2,
+            ..MqttConfig::default()
+        };
+        // nothing receives, so no event loop drains the request channel
+        let transport = MqttTransport::new(&config);
+        let started = Instant::now();
+        for _ in 0..config.request_capacity {
+            transport.send_frame("Tank", "@TNK#").unwrap();
+        }
+        let error = transport.send_frame("Tank", "@TNK#").unwrap_err();
+        assert!(error.to_string().contains("queue is full"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,344 @@
+use super::{ConnectionHandler, ConnectionState, FrameHandler, FrameTransport};
+use crate::config::SerialConfig;
+use crate::module_layout::ModuleLayouts;
+use std::{collections::{HashMap, HashSet}, io::{ErrorKind, Read, Write}, sync::{Arc, Mutex}, time::Duration};
+use anyhow::{Result, Error, Context, anyhow};
+use log::{error, warn};
+use serialport::SerialPort;
+
+/// first and last character of every frame
+const FRAME_START: u8 = b'@';
+const FRAME_END: u8 = b'#';
+
+/// frames travel raw over a serial port, incoming frames are routed by their header tag
+pub struct SerialTransport {
+    port_name: String,
+    writer: Mutex<Box<dyn SerialPort>>,
+    reader: Mutex<Option<Box<dyn SerialPort>>>,
+    /// output header tag (e.g. `@TNK`) to namespace of every module in the layout file
+    namespaces_by_tag: HashMap<String, String>,
+    subscribed: Arc<Mutex<HashSet<String>>>,
+    max_frame_length: usize,
+    state: Arc<ConnectionState>,
+}
+
+impl SerialTransport {
+    pub fn open(config: &SerialConfig, module_layouts: &ModuleLayouts) -> Result<Self, Error> {
+        let writer = serialport::new(config.port.as_str(), config.baud_rate)
+            .timeout(Duration::from_millis(config.read_timeout_ms))
+            .open()
+            .with_context(|| format!("cannot open serial port '{}'", config.port))?;
+        let reader = writer
+            .try_clone()
+            .with_context(|| format!("cannot clone serial port '{}'", config.port))?;
+
+        let namespaces_by_tag = module_layouts.module
+            .iter()
+            .map(|layout| (layout.output.header.content(), layout.name.clone()))
+            .collect();
+        let max_frame_length = module_layouts.module
+            .iter()
+            .map(|layout| layout.output_format().tail.end.index + 1)
+            .max()
+            .unwrap_or_default();
+
+        // the port is up once opened and only goes down when reading fails
+        let state = Arc::new(ConnectionState::default());
+        state.set(true);
+
+        Ok(Self {
+            port_name: config.port.clone(),
+            writer: Mutex::new(writer),
+            reader: Mutex::new(Some(reader)),
+            namespaces_by_tag,
+            subscribed: Arc::new(Mutex::new(HashSet::new())),
+            max_frame_length,
+            state,
+        })
+    }
+}
+
+impl FrameTransport for SerialTransport {
+    fn send_frame(&self, _namespace: &str, frame: &str) -> Result<(), Error> {
+        // the header tag inside the frame addresses the module
+        match self.writer.lock() {
+            Ok(mut writer) => {
+                writer.write_all(frame.as_bytes())?;
+                writer.flush()?;
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Failed to acquire serial port lock")),
+        }
+    }
+
+    fn subscribe(&self, namespace: &str) -> Result<(), Error> {
+        if !self.namespaces_by_tag.values().any(|name| name == namespace) {
+            return Err(anyhow!("namespace '{}' has no module layout", namespace));
+        }
+        match self.subscribed.lock() {
+            Ok(mut subscribed) => {
+                subscribed.insert(namespace.to_string());
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Failed to acquire serial subscription lock")),
+        }
+    }
+
+    fn start_receiving(&self, handler: FrameHandler) -> Result<(), Error> {
+        let mut reader = self.reader
+            .lock()
+            .map_err(|_| anyhow!("Failed to acquire serial port lock"))?
+            .take()
+            .ok_or_else(|| anyhow!("serial transport is already receiving"))?;
+
+        let port_name = self.port_name.clone();
+        let namespaces_by_tag = self.namespaces_by_tag.clone();
+        let subscribed = self.subscribed.clone();
+        let max_frame_length = self.max_frame_length;
+        let state = self.state.clone();
+
+        std::thread::spawn(move || {
+            let mut delimiter = FrameDelimiter::new(max_frame_length);
+            let mut buf = [0u8; 64];
+
+            loop {
+                let count = match reader.read(&mut buf) {
+                    Ok(count) => count,
+                    Err(e) if e.kind() == ErrorKind::TimedOut => {
+                        // a frame is sent at once, a pause inside it means it was cut
+                        if let Some(frame) = delimiter.cut() {
+                            warn!("{}: dropped incomplete frame: {}", port_name, String::from_utf8_lossy(&frame));
+                        }
+                        continue;
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        error!("{}: serial read failed, stop receiving: {}", port_name, e);
+                        state.set(false);
+                        return;
+                    }
+                };
+
+                for &byte in &buf[..count] {
+                    match delimiter.push(byte) {
+                        Delimited::Pending => {}
+                        Delimited::Frame(frame) => {
+                            dispatch_frame(&port_name, &frame, &namespaces_by_tag, &subscribed, &handler);
+                        }
+                        Delimited::Incomplete(frame) => {
+                            warn!("{}: dropped incomplete frame: {}", port_name, String::from_utf8_lossy(&frame));
+                        }
+                        Delimited::TooLong => {
+                            warn!("{}: dropped frame longer than {} bytes", port_name, max_frame_length);
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn watch_connection(&self, handler: ConnectionHandler) -> Result<(), Error> {
+        self.state.watch(handler)
+    }
+
+    fn is_connected(&self) -> bool {
+        self.state.is_connected()
+    }
+}
+
+/// result of feeding one byte to a `FrameDelimiter`
+#[derive(Debug, PartialEq, Eq)]
+enum Delimited {
+    /// byte outside a frame or inside an unfinished one
+    Pending,
+    /// frame from its start to its end marker
+    Frame(Vec<u8>),
+    /// unfinished frame cut by the start of the next one
+    Incomplete(Vec<u8>),
+    /// frame dropped once longer than the longest module frame
+    TooLong,
+}
+
+/// splits the serial byte stream into frames from `FRAME_START` to `FRAME_END`
+struct FrameDelimiter {
+    frame: Vec<u8>,
+    in_frame: bool,
+    max_frame_length: usize,
+}
+
+impl FrameDelimiter {
+    fn new(max_frame_length: usize) -> Self {
+        Self { frame: Vec::with_capacity(max_frame_length), in_frame: false, max_frame_length }
+    }
+
+    fn push(&mut self, byte: u8) -> Delimited {
+        let mut result = Delimited::Pending;
+        if byte == FRAME_START {
+            if let Some(frame) = self.cut() {
+                result = Delimited::Incomplete(frame);
+            }
+            self.in_frame = true;
+        }
+        if !self.in_frame {
+            return result;
+        }
+
+        self.frame.push(byte);
+        if byte == FRAME_END {
+            self.in_frame = false;
+            Delimited::Frame(std::mem::take(&mut self.frame))
+        } else if self.frame.len() > self.max_frame_length {
+            self.frame.clear();
+            self.in_frame = false;
+            Delimited::TooLong
+        } else {
+            result
+        }
+    }
+
+    /// drop the unfinished frame, if any, and return it
+    fn cut(&mut self) -> Option<Vec<u8>> {
+        if !self.in_frame {
+            return None;
+        }
+        self.in_frame = false;
+        Some(std::mem::take(&mut self.frame))
+    }
+}
+
+fn dispatch_frame(
+    port_name: &str,
+    frame: &[u8],
+    namespaces_by_tag: &HashMap<String, String>,
+    subscribed: &Mutex<HashSet<String>>,
+    handler: &FrameHandler,
+) {
+    let frame = match std::str::from_utf8(frame) {
+        Ok(frame) => frame,
+        Err(_) => {
+            warn!("{}: cannot convert bytes to String", port_name);
+            return;
+        }
+    };
+
+    let namespace = namespaces_by_tag
+        .iter()
+        .find(|(tag, _)| frame.starts_with(tag.as_str()))
+        .map(|(_, namespace)| namespace);
+
+    match namespace {
+        Some(namespace) => {
+            let is_subscribed = subscribed
+                .lock()
+                .map(|subscribed| subscribed.contains(namespace))
+                .unwrap_or(false);
+            if is_subscribed {
+                handler(namespace, frame);
+            }
+        }
+        None => warn!("{}: no module for frame: {}", port_name, frame),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(delimiter: &mut FrameDelimiter, bytes: &str) -> Vec<Delimited> {
+        bytes
+            .bytes()
+            .map(|byte| delimiter.push(byte))
+            .filter(|result| *result != Delimited::Pending)
+            .collect()
+    }
+
+    fn frame(frame: &str) -> Delimited {
+        Delimited::Frame(frame.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn splits_back_to_back_frames() {
+        let mut delimiter = FrameDelimiter::new(32);
+        let results = feed(&mut delimiter, "@TNK0001#@COF0002#");
+        assert_eq!(results, vec![frame("@TNK0001#"), frame("@COF0002#")]);
+    }
+
+    #[test]
+    fn skips_noise_between_frames() {
+        let mut delimiter = FrameDelimiter::new(32);
+        let results = feed(&mut delimiter, "\r\nxx@TNK0001#\n#zz@COF0002#");
+        assert_eq!(results, vec![frame("@TNK0001#"), frame("@COF0002#")]);
+    }
+
+    #[test]
+    fn joins_frames_split_across_reads() {
+        let mut delimiter = FrameDelimiter::new(32);
+        assert!(feed(&mut delimiter, "@TNK00").is_empty());
+        assert_eq!(feed(&mut delimiter, "01#"), vec![frame("@TNK0001#")]);
+    }
+
+    #[test]
+    fn start_marker_drops_unfinished_frame() {
+        let mut delimiter = FrameDelimiter::new(32);
+        let results = feed(&mut delimiter, "@TNK00@COF0002#");
+        assert_eq!(results, vec![Delimited::Incomplete(b"@TNK00".to_vec()), frame("@COF0002#")]);
+    }
+
+    #[test]
+    fn drops_frames_longer_than_the_maximum() {
+        let mut delimiter = FrameDelimiter::new(8);
+        let results = feed(&mut delimiter, "@TNK000001#@TNK01#");
+        assert_eq!(results, vec![Delimited::TooLong, frame("@TNK01#")]);
+    }
+
+    #[test]
+    fn cut_drops_unfinished_frame_once() {
+        let mut delimiter = FrameDelimiter::new(32);
+        feed(&mut delimiter, "@TNK00");
+        assert_eq!(delimiter.cut(), Some(b"@TNK00".to_vec()));
+        assert_eq!(delimiter.cut(), None);
+        assert!(feed(&mut delimiter, "01#").is_empty());
+    }
+
+    #[test]
+    fn round_trips_frames_over_a_pseudo_terminal() {
+        let (mut module, port) = serialport::TTYPort::pair().unwrap();
+        module.set_timeout(Duration::from_secs(1)).unwrap();
+        // the transport locks the port exclusively, close the pair's end of it first
+        let config = SerialConfig { port: port.name().unwrap(), baud_rate: 115200, read_timeout_ms: 100 };
+        drop(port);
+        let module_layouts = ModuleLayouts::from_toml(include_str!("../../config/modules.toml")).unwrap();
+        let transport = SerialTransport::open(&config, &module_layouts).unwrap();
+        assert!(transport.is_connected());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        transport.subscribe("light").unwrap();
+        transport
+            .start_receiving(Box::new(move |namespace, frame| {
+                sender.lock().unwrap().send((namespace.to_string(), frame.to_string())).unwrap();
+            }))
+            .unwrap();
+
+        // frames of unsubscribed modules and noise are dropped, a frame split across writes is joined
+        module.write_all(b"\r\n@TNK000006100000AB#@LGT0000061").unwrap();
+        module.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        module.write_all(b"00000AB#").unwrap();
+        module.flush().unwrap();
+        let received = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(received, ("light".to_string(), "@LGT000006100000AB#".to_string()));
+
+        transport.send_frame("light", "@LGT000007010000100CD#").unwrap();
+        let mut sent = Vec::new();
+        let mut buf = [0u8; 64];
+        while !sent.ends_with(b"#") {
+            let count = module.read(&mut buf).unwrap();
+            sent.extend_from_slice(&buf[..count]);
+        }
+        assert_eq!(sent, b"@LGT000007010000100CD#");
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use super::{FrameHandler, FrameTransport};
+use crate::config::SimulatorConfig;
+use crate::simulator::{SetFrame, Simulator};
+use std::{collections::HashSet, sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex}, time::Duration};
+use anyhow::{Result, Error, anyhow};
+
+/// link to simulated modules running in a thread of the driver, answers arrive
+/// asynchronously like on a real link
+pub struct SimulatorTransport {
+    simulator: Mutex<Option<(Simulator, Receiver<SetFrame>)>>,
+    sender: Mutex<Sender<SetFrame>>,
+    subscribed: Arc<Mutex<HashSet<String>>>,
+    period: Duration,
+}
+
+impl SimulatorTransport {
+    pub fn new(simulator: Simulator, config: &SimulatorConfig) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            simulator: Mutex::new(Some((simulator, receiver))),
+            sender: Mutex::new(sender),
+            subscribed: Arc::new(Mutex::new(HashSet::new())),
+            period: config.period(),
+        }
+    }
+}
+
+impl FrameTransport for SimulatorTransport {
+    fn send_frame(&self, namespace: &str, frame: &str) -> Result<(), Error> {
+        match self.sender.lock() {
+            Ok(sender) => sender
+                .send((namespace.to_string(), frame.to_string()))
+                .map_err(|_| anyhow!("simulator is not running")),
+            Err(_) => Err(anyhow!("Failed to acquire simulator sender lock")),
+        }
+    }
+
+    fn subscribe(&self, namespace: &str) -> Result<(), Error> {
+        match self.subscribed.lock() {
+            Ok(mut subscribed) => {
+                subscribed.insert(namespace.to_string());
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Failed to acquire simulator subscription lock")),
+        }
+    }
+
+    fn start_receiving(&self, handler: FrameHandler) -> Result<(), Error> {
+        let (mut simulator, receiver) = self.simulator
+            .lock()
+            .map_err(|_| anyhow!("Failed to acquire simulator lock"))?
+            .take()
+            .ok_or_else(|| anyhow!("simulator transport is already receiving"))?;
+        let subscribed = self.subscribed.clone();
+        let period = self.period;
+
+        std::thread::spawn(move || {
+            simulator.run(period, receiver, |namespace, frame| {
+                // frames of namespaces nobody subscribed to are dropped like on a real link
+                let is_subscribed = subscribed
+                    .lock()
+                    .map(|subscribed| subscribed.contains(namespace))
+                    .unwrap_or(false);
+                if is_subscribed {
+                    handler(namespace, frame);
+                }
+            });
+        });
+
+        Ok(())
+    }
+}